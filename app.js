@@ -0,0 +1,30 @@
+// Registers the service worker which keeps the pages readable offline, and asks it
+// to send the saves and deletes queued offline once the connection is back. Those
+// it couldn't send are shown, they stay queued for the next try
+if ('serviceWorker' in navigator) {
+  navigator.serviceWorker.register('/sw.js');
+
+  navigator.serviceWorker.addEventListener('message', function(event) {
+    if (!event.data || event.data.type !== 'unsent') {
+      return;
+    }
+
+    const notice = document.createElement('div');
+    notice.className = 'notice';
+    notice.textContent = event.data.status === 401 || event.data.status === 403
+      ? 'Changes made offline were refused, sign in again to send them.'
+      : 'Changes made offline could not be sent yet, they are tried again later.';
+    document.body.prepend(notice);
+  });
+
+  const flush = function() {
+    navigator.serviceWorker.ready.then(function(registration) {
+      registration.active.postMessage('flush');
+    });
+  };
+
+  if (navigator.onLine) {
+    flush();
+  }
+  window.addEventListener('online', flush);
+}
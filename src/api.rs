@@ -0,0 +1,702 @@
+use crate::App;
+use crate::BulkAction;
+use crate::EditForm;
+use crate::FeedItem;
+use crate::FetchError;
+use crate::InvalidEdit;
+use crate::NoteKind;
+use crate::add_article;
+use crate::bulk_update;
+use crate::collection;
+use crate::collection::Collection;
+use crate::collection::CollectionItem;
+use crate::csrf;
+use crate::delete_article;
+use crate::edit_article;
+use crate::expire_old_articles;
+use crate::fetch_feed_item;
+use crate::fetch_feed_page;
+use crate::filter::FeedFilter;
+use crate::page::Page;
+use crate::page::PageQuery;
+use crate::user::User;
+use axum::Extension;
+use axum::Json;
+use axum::extract::Path;
+use axum::extract::Query;
+use axum::extract::State;
+use axum::extract::rejection::JsonRejection;
+use axum::extract::rejection::PathRejection;
+use axum::extract::rejection::QueryRejection;
+use axum::http::HeaderMap;
+use axum::http::StatusCode;
+use axum::http::header::CONTENT_TYPE;
+use axum::response::IntoResponse;
+use axum::response::Response;
+use log::error;
+use log::info;
+use serde::Deserialize;
+use serde::Serialize;
+use tokio::task::JoinSet;
+
+/// Articles fetched at the same time by a batch save
+const BATCH_CONCURRENCY: usize = 4;
+
+const MAX_BATCH_URLS: usize = 500;
+
+/// Error body of every failed API request
+#[derive(Debug, Serialize)]
+pub struct ApiError {
+    #[serde(skip)]
+    status: StatusCode,
+    error: &'static str,
+    message: String,
+}
+
+impl ApiError {
+    fn bad_request(message: impl ToString) -> Self {
+        Self {
+            status: StatusCode::BAD_REQUEST,
+            error: "bad_request",
+            message: message.to_string(),
+        }
+    }
+
+    fn unsupported_media_type() -> Self {
+        Self {
+            status: StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            error: "unsupported_media_type",
+            message: "send application/json from other sites".to_string(),
+        }
+    }
+
+    fn not_found(guid: &str) -> Self {
+        Self {
+            status: StatusCode::NOT_FOUND,
+            error: "not_found",
+            message: format!("no article {guid}"),
+        }
+    }
+
+    /// Collections are only known to their members
+    fn no_collection(name: &str) -> Self {
+        Self {
+            status: StatusCode::NOT_FOUND,
+            error: "not_found",
+            message: format!("no collection {name}"),
+        }
+    }
+
+    /// Only failures to fetch the link are the client's to see, storing it is ours
+    fn save_failed(err: anyhow::Error) -> Self {
+        match err.downcast::<FetchError>() {
+            Ok(err) => Self {
+                status: StatusCode::BAD_GATEWAY,
+                error: "fetch_failed",
+                message: err.to_string(),
+            },
+            Err(err) => Self::internal(err),
+        }
+    }
+
+    /// Only invalid values are the client's to see, storing them is ours
+    fn edit_failed(err: anyhow::Error) -> Self {
+        match err.downcast::<InvalidEdit>() {
+            Ok(err) => Self::bad_request(err),
+            Err(err) => Self::internal(err),
+        }
+    }
+
+    /// The cause is logged but not sent to the client
+    fn internal(err: anyhow::Error) -> Self {
+        error!("{err}");
+        Self {
+            status: StatusCode::INTERNAL_SERVER_ERROR,
+            error: "internal",
+            message: "internal error".to_string(),
+        }
+    }
+}
+
+impl From<JsonRejection> for ApiError {
+    fn from(rejection: JsonRejection) -> Self {
+        Self::bad_request(rejection.body_text())
+    }
+}
+
+impl From<QueryRejection> for ApiError {
+    fn from(rejection: QueryRejection) -> Self {
+        Self::bad_request(rejection.body_text())
+    }
+}
+
+impl From<PathRejection> for ApiError {
+    fn from(rejection: PathRejection) -> Self {
+        Self::bad_request(rejection.body_text())
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.status, Json(self)).into_response()
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ApiArticle {
+    guid: String,
+    title: String,
+    link: String,
+    saved: String,
+    expires: String,
+    tags: Vec<String>,
+    notes: Vec<ApiNote>,
+}
+
+#[derive(Debug, Serialize)]
+struct ApiNote {
+    id: i64,
+    kind: NoteKind,
+    text: String,
+}
+
+impl From<FeedItem> for ApiArticle {
+    fn from(item: FeedItem) -> Self {
+        Self {
+            guid: item.guid,
+            title: item.title,
+            link: item.link,
+            saved: item.pub_date,
+            expires: item.expire_date,
+            tags: item.tags,
+            notes: item
+                .notes
+                .into_iter()
+                .map(|note| ApiNote {
+                    id: note.id,
+                    kind: note.kind,
+                    text: note.text,
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ApiArticleList {
+    articles: Vec<ApiArticle>,
+    /// Query string of the next page
+    #[serde(skip_serializing_if = "Option::is_none")]
+    next: Option<String>,
+}
+
+/// Article of a shared collection and the member who saved it
+#[derive(Debug, Serialize)]
+pub struct ApiCollectionArticle {
+    guid: String,
+    title: String,
+    link: String,
+    saved: String,
+    expires: String,
+    saved_by: String,
+}
+
+impl From<CollectionItem> for ApiCollectionArticle {
+    fn from(item: CollectionItem) -> Self {
+        Self {
+            guid: item.guid,
+            title: item.title,
+            link: item.link,
+            saved: item.pub_date,
+            expires: item.expire_date,
+            saved_by: item.saved_by,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ApiCollectionArticleList {
+    articles: Vec<ApiCollectionArticle>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateArticle {
+    url: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct BatchSave {
+    urls: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BatchResults {
+    results: Vec<BatchResult>,
+}
+
+/// Outcome of saving one of the batch's urls, either a guid or an error
+#[derive(Debug, Serialize)]
+struct BatchResult {
+    url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    guid: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// Articles are either listed by guid or selected by a filter
+#[derive(Debug, Deserialize)]
+pub struct BulkRequest {
+    #[serde(flatten)]
+    action: BulkAction,
+    guids: Option<Vec<String>>,
+    filter: Option<FeedFilter>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BulkResult {
+    matched: usize,
+    changed: usize,
+}
+
+/// Fields left out stay as they are, tags replace the current ones
+#[derive(Debug, Deserialize)]
+pub struct UpdateArticle {
+    title: Option<String>,
+    link: Option<String>,
+    tags: Option<Vec<String>>,
+    pinned: Option<bool>,
+}
+
+pub async fn handle_list_articles(
+    State(state): State<App>,
+    Extension(user): Extension<User>,
+    page: Result<Query<PageQuery>, QueryRejection>,
+    filter: Result<Query<FeedFilter>, QueryRejection>,
+) -> Result<Json<ApiArticleList>, ApiError> {
+    let Query(page) = page?;
+    let Query(filter) = filter?;
+
+    info!("api_list_articles: {} {page:?} {filter:?}", user.name);
+
+    let page = Page::new(&page, state.args.feed_limit);
+
+    let mut db_lock = state.db_connection.lock().unwrap();
+
+    expire_old_articles(&mut db_lock, &user).map_err(ApiError::internal)?;
+    let (items, pagination) =
+        fetch_feed_page(&mut db_lock, &user, &page, &filter).map_err(ApiError::internal)?;
+
+    Ok(Json(ApiArticleList {
+        articles: items.into_iter().map(ApiArticle::from).collect(),
+        next: pagination.next,
+    }))
+}
+
+pub async fn handle_get_article(
+    State(state): State<App>,
+    Extension(user): Extension<User>,
+    guid: Result<Path<String>, PathRejection>,
+) -> Result<Json<ApiArticle>, ApiError> {
+    let Path(guid) = guid?;
+
+    info!("api_get_article: {} {guid}", user.name);
+
+    get_article(&state, &user, &guid).map(Json)
+}
+
+pub async fn handle_create_article(
+    State(state): State<App>,
+    Extension(user): Extension<User>,
+    create: Result<Json<CreateArticle>, JsonRejection>,
+) -> Result<(StatusCode, Json<ApiArticle>), ApiError> {
+    let Json(create) = create?;
+
+    info!("api_create_article: {} {create:?}", user.name);
+
+    let guid = add_article(&create.url, None, &user, state.db_connection.clone())
+        .await
+        .map_err(ApiError::save_failed)?;
+
+    get_article(&state, &user, &guid).map(|article| (StatusCode::CREATED, Json(article)))
+}
+
+pub async fn handle_update_article(
+    State(state): State<App>,
+    Extension(user): Extension<User>,
+    guid: Result<Path<String>, PathRejection>,
+    update: Result<Json<UpdateArticle>, JsonRejection>,
+) -> Result<Json<ApiArticle>, ApiError> {
+    let Path(guid) = guid?;
+    let Json(update) = update?;
+
+    info!("api_update_article: {} {guid} {update:?}", user.name);
+
+    get_article(&state, &user, &guid)?;
+
+    let edit = EditForm {
+        guid: guid.clone(),
+        title: update.title,
+        link: update.link,
+        tags: update.tags.map(|tags| tags.join(",")),
+        pinned: update.pinned,
+    };
+
+    {
+        let mut db_lock = state.db_connection.lock().unwrap();
+        edit_article(&mut db_lock, &user, &edit).map_err(ApiError::edit_failed)?;
+    }
+
+    get_article(&state, &user, &guid).map(Json)
+}
+
+pub async fn handle_delete_article(
+    State(state): State<App>,
+    Extension(user): Extension<User>,
+    guid: Result<Path<String>, PathRejection>,
+) -> Result<StatusCode, ApiError> {
+    let Path(guid) = guid?;
+
+    info!("api_delete_article: {} {guid}", user.name);
+
+    get_article(&state, &user, &guid)?;
+
+    let mut db_lock = state.db_connection.lock().unwrap();
+    delete_article(&mut db_lock, &user, &guid).map_err(ApiError::internal)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Saves urls from a JSON body or from a body with one url per line. Other sites can
+/// post plain text without asking first, so lines are only read from scripts and
+/// this site
+pub async fn handle_batch_save(
+    State(state): State<App>,
+    Extension(user): Extension<User>,
+    headers: HeaderMap,
+    body: String,
+) -> Result<Json<BatchResults>, ApiError> {
+    let is_json = headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("application/json"));
+
+    if !is_json && !csrf::is_same_site(&headers, &state.args.link) {
+        return Err(ApiError::unsupported_media_type());
+    }
+
+    let urls = if is_json {
+        serde_json::from_str::<BatchSave>(&body)
+            .map_err(ApiError::bad_request)?
+            .urls
+    } else {
+        body.lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(ToString::to_string)
+            .collect()
+    };
+
+    info!("api_batch_save: {} {} urls", user.name, urls.len());
+
+    if urls.len() > MAX_BATCH_URLS {
+        return Err(ApiError::bad_request(format!(
+            "more than {MAX_BATCH_URLS} urls"
+        )));
+    }
+
+    let mut results = Vec::with_capacity(urls.len());
+    let mut tasks = JoinSet::new();
+
+    for (index, url) in urls.into_iter().enumerate() {
+        if tasks.len() >= BATCH_CONCURRENCY
+            && let Some(joined) = tasks.join_next().await
+        {
+            results.push(joined.map_err(|err| ApiError::internal(err.into()))?);
+        }
+
+        let db_connection = state.db_connection.clone();
+        let user = user.clone();
+        tasks.spawn(async move {
+            let result = add_article(&url, None, &user, db_connection).await;
+            (index, url, result)
+        });
+    }
+
+    while let Some(joined) = tasks.join_next().await {
+        results.push(joined.map_err(|err| ApiError::internal(err.into()))?);
+    }
+
+    results.sort_by_key(|(index, _, _)| *index);
+
+    let results = results
+        .into_iter()
+        .map(|(_, url, result)| match result {
+            Ok(guid) => BatchResult {
+                url,
+                guid: Some(guid),
+                error: None,
+            },
+            Err(err) => BatchResult {
+                url,
+                guid: None,
+                error: Some(ApiError::save_failed(err).message),
+            },
+        })
+        .collect();
+
+    Ok(Json(BatchResults { results }))
+}
+
+pub async fn handle_bulk_update(
+    State(state): State<App>,
+    Extension(user): Extension<User>,
+    bulk: Result<Json<BulkRequest>, JsonRejection>,
+) -> Result<Json<BulkResult>, ApiError> {
+    let Json(bulk) = bulk?;
+
+    info!("api_bulk_update: {} {bulk:?}", user.name);
+
+    let mut db_lock = state.db_connection.lock().unwrap();
+
+    let guids = match (bulk.guids, bulk.filter) {
+        (Some(guids), None) => guids,
+        (None, Some(filter)) => {
+            let everything = Page {
+                limit: u32::MAX,
+                number: 1,
+                before: None,
+            };
+
+            expire_old_articles(&mut db_lock, &user).map_err(ApiError::internal)?;
+            let (items, _) = fetch_feed_page(&mut db_lock, &user, &everything, &filter)
+                .map_err(ApiError::internal)?;
+
+            items.into_iter().map(|item| item.guid).collect()
+        }
+        _ => {
+            return Err(ApiError::bad_request(
+                "either guids or a filter is required",
+            ));
+        }
+    };
+
+    let changed =
+        bulk_update(&mut db_lock, &user, &guids, &bulk.action).map_err(ApiError::internal)?;
+
+    Ok(Json(BulkResult {
+        matched: guids.len(),
+        changed,
+    }))
+}
+
+pub async fn handle_list_collection_articles(
+    State(state): State<App>,
+    Extension(user): Extension<User>,
+    name: Result<Path<String>, PathRejection>,
+) -> Result<Json<ApiCollectionArticleList>, ApiError> {
+    let Path(name) = name?;
+
+    info!("api_list_collection_articles: {} {name}", user.name);
+
+    let collection = get_collection(&state, &user, &name)?;
+    let items = get_collection_items(&state, &collection)?;
+
+    Ok(Json(ApiCollectionArticleList {
+        articles: items.into_iter().map(ApiCollectionArticle::from).collect(),
+    }))
+}
+
+pub async fn handle_create_collection_article(
+    State(state): State<App>,
+    Extension(user): Extension<User>,
+    name: Result<Path<String>, PathRejection>,
+    create: Result<Json<CreateArticle>, JsonRejection>,
+) -> Result<(StatusCode, Json<ApiCollectionArticle>), ApiError> {
+    let Path(name) = name?;
+    let Json(create) = create?;
+
+    info!(
+        "api_create_collection_article: {} {name} {create:?}",
+        user.name
+    );
+
+    let collection = get_collection(&state, &user, &name)?;
+    let guid = collection::add_to_collection(
+        &create.url,
+        None,
+        &collection,
+        &user,
+        state.db_connection.clone(),
+    )
+    .await
+    .map_err(ApiError::save_failed)?;
+
+    get_collection_items(&state, &collection)?
+        .into_iter()
+        .find(|item| item.guid == guid)
+        .map(|item| (StatusCode::CREATED, Json(ApiCollectionArticle::from(item))))
+        .ok_or_else(|| ApiError::not_found(&guid))
+}
+
+pub async fn handle_delete_collection_article(
+    State(state): State<App>,
+    Extension(user): Extension<User>,
+    path: Result<Path<(String, String)>, PathRejection>,
+) -> Result<StatusCode, ApiError> {
+    let Path((name, guid)) = path?;
+
+    info!("api_delete_collection_article: {} {name} {guid}", user.name);
+
+    let collection = get_collection(&state, &user, &name)?;
+
+    let mut db_lock = state.db_connection.lock().unwrap();
+    if collection::remove_from_collection(&mut db_lock, &collection, &guid)
+        .map_err(ApiError::internal)?
+    {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(ApiError::not_found(&guid))
+    }
+}
+
+/// A collection the user is a member of
+fn get_collection(state: &App, user: &User, name: &str) -> Result<Collection, ApiError> {
+    let mut db_lock = state.db_connection.lock().unwrap();
+
+    collection::fetch_member_collection(&mut db_lock, name, user, &state.args)
+        .map_err(ApiError::internal)?
+        .ok_or_else(|| ApiError::no_collection(name))
+}
+
+fn get_collection_items(
+    state: &App,
+    collection: &Collection,
+) -> Result<Vec<CollectionItem>, ApiError> {
+    let mut db_lock = state.db_connection.lock().unwrap();
+
+    collection::expire_collection_articles(&mut db_lock, &state.args)
+        .map_err(ApiError::internal)?;
+    collection::fetch_collection_items(&mut db_lock, collection, state.args.feed_limit)
+        .map_err(ApiError::internal)
+}
+
+/// A live article of the user, articles in trash or of others are not found
+fn get_article(state: &App, user: &User, guid: &str) -> Result<ApiArticle, ApiError> {
+    let mut db_lock = state.db_connection.lock().unwrap();
+
+    expire_old_articles(&mut db_lock, user).map_err(ApiError::internal)?;
+    fetch_feed_item(&mut db_lock, user, guid)
+        .map_err(ApiError::internal)?
+        .map(ApiArticle::from)
+        .ok_or_else(|| ApiError::not_found(guid))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::FetchError;
+    use crate::InvalidEdit;
+    use crate::api::ApiError;
+    use crate::api::handle_batch_save;
+    use crate::filter::FeedFilter;
+    use crate::tests::app;
+    use crate::tests::user;
+    use anyhow::anyhow;
+    use axum::Extension;
+    use axum::body::to_bytes;
+    use axum::extract::Query;
+    use axum::extract::State;
+    use axum::http::HeaderMap;
+    use axum::http::HeaderValue;
+    use axum::http::StatusCode;
+    use axum::http::Uri;
+    use axum::response::IntoResponse;
+
+    #[tokio::test]
+    async fn errors_are_json() {
+        let response = ApiError::not_found("guid").into_response();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["error"], "not_found");
+        assert_eq!(body["message"], "no article guid");
+        assert!(body.get("status").is_none());
+    }
+
+    #[tokio::test]
+    async fn rejected_queries_are_json() {
+        let uri: Uri = "/api/v1/articles?sort=random".parse().unwrap();
+        let rejection = Query::<FeedFilter>::try_from_uri(&uri).unwrap_err();
+
+        let response = ApiError::from(rejection).into_response();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["error"], "bad_request");
+    }
+
+    #[test]
+    fn only_fetch_failures_are_shown() {
+        let fetch = ApiError::save_failed(FetchError(anyhow!("connection refused")).into());
+        assert_eq!(fetch.status, StatusCode::BAD_GATEWAY);
+        assert_eq!(fetch.error, "fetch_failed");
+        assert_eq!(fetch.message, "connection refused");
+
+        let store = ApiError::save_failed(anyhow!("database is locked"));
+        assert_eq!(store.status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(store.error, "internal");
+        assert_eq!(store.message, "internal error");
+    }
+
+    #[test]
+    fn only_invalid_edits_are_shown() {
+        let invalid = ApiError::edit_failed(InvalidEdit("invalid link".to_string()).into());
+        assert_eq!(invalid.status, StatusCode::BAD_REQUEST);
+        assert_eq!(invalid.message, "invalid link");
+
+        let store = ApiError::edit_failed(anyhow!("database is locked"));
+        assert_eq!(store.status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(store.message, "internal error");
+    }
+
+    #[tokio::test]
+    async fn plain_batches_only_from_this_site() {
+        let app = app();
+        let mut headers = HeaderMap::new();
+        headers.insert("Content-Type", HeaderValue::from_static("text/plain"));
+
+        let batch = handle_batch_save(
+            State(app.clone()),
+            Extension(user(0)),
+            headers.clone(),
+            String::new(),
+        )
+        .await;
+        assert!(batch.is_ok());
+
+        headers.insert("Origin", HeaderValue::from_static("https://evil.example"));
+        let batch = handle_batch_save(
+            State(app.clone()),
+            Extension(user(0)),
+            headers.clone(),
+            String::new(),
+        )
+        .await;
+        assert_eq!(
+            batch.unwrap_err().status,
+            StatusCode::UNSUPPORTED_MEDIA_TYPE
+        );
+
+        headers.insert("Content-Type", HeaderValue::from_static("application/json"));
+        let batch = handle_batch_save(
+            State(app),
+            Extension(user(0)),
+            headers,
+            "{\"urls\": []}".to_string(),
+        )
+        .await;
+        assert!(batch.is_ok());
+    }
+}
@@ -0,0 +1,233 @@
+use crate::FeedItem;
+use crate::FeedWriter;
+use crate::markup::Markup;
+use crate::page::Pagination;
+use crate::page::page_link;
+use crate::push_notes;
+use crate::search::SearchQuery;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+use url::Url;
+
+pub struct AtomWriter {
+    buffer: Markup,
+    link: String,
+    path: String,
+}
+
+impl FeedWriter for AtomWriter {
+    const CONTENT_TYPE: &str = "application/atom+xml";
+    const PATH: &str = "feed.atom";
+
+    fn new_at(title: &str, description: &str, link: &str, path: &str, time: SystemTime) -> Self {
+        let mut buffer = Markup::new();
+
+        buffer.push_raw("<?xml version=\"1.0\" encoding=\"utf-8\"?>");
+        buffer.push_raw("<feed xmlns=\"http://www.w3.org/2005/Atom\">");
+
+        buffer.push_raw("<title type=\"text\">");
+        buffer.push_text(title);
+        buffer.push_raw("</title>");
+
+        buffer.push_raw("<subtitle type=\"text\">");
+        buffer.push_text(description);
+        buffer.push_raw("</subtitle>");
+
+        buffer.push_raw("<id>");
+        buffer.push_text(link);
+        buffer.push_raw("</id>");
+
+        buffer.push_raw("<link rel=\"alternate\" href=\"");
+        buffer.push_url(link);
+        buffer.push_raw("\"/>");
+
+        if let Some(self_link) = Url::parse(link).ok().and_then(|url| url.join(path).ok()) {
+            buffer.push_raw("<link rel=\"self\" type=\"application/atom+xml\" href=\"");
+            buffer.push_url(self_link.as_str());
+            buffer.push_raw("\"/>");
+        }
+
+        buffer.push_raw("<updated>");
+        buffer.push_text(&fmt_rfc3339(time));
+        buffer.push_raw("</updated>");
+
+        buffer.push_raw("<author><name>");
+        buffer.push_text(title);
+        buffer.push_raw("</name></author>");
+
+        buffer.push_raw("<generator>paket</generator>");
+
+        Self {
+            buffer,
+            link: link.to_string(),
+            path: path.to_string(),
+        }
+    }
+
+    fn write_feed_items(&mut self, items: Vec<FeedItem>) {
+        let buffer = &mut self.buffer;
+
+        for item in items {
+            let date = fmt_rfc3339(item.pub_time);
+
+            buffer.push_raw("<entry>");
+
+            buffer.push_raw("<id>urn:uuid:");
+            buffer.push_text(&item.guid);
+            buffer.push_raw("</id>");
+
+            buffer.push_raw("<title type=\"text\">");
+            buffer.push_text(&item.title);
+            buffer.push_raw("</title>");
+
+            buffer.push_raw("<link rel=\"alternate\" href=\"");
+            buffer.push_url(&item.link);
+            buffer.push_raw("\"/>");
+
+            buffer.push_raw("<published>");
+            buffer.push_text(&date);
+            buffer.push_raw("</published>");
+
+            buffer.push_raw("<updated>");
+            buffer.push_text(&date);
+            buffer.push_raw("</updated>");
+
+            for tag in &item.tags {
+                buffer.push_raw("<category term=\"");
+                buffer.push_text(tag);
+                buffer.push_raw("\"/>");
+            }
+
+            buffer.push_raw("<summary type=\"text\">Expires: ");
+            buffer.push_text(&item.expire_date);
+            buffer.push_raw("</summary>");
+
+            if !item.notes.is_empty() {
+                let mut content = Markup::new();
+                push_notes(&mut content, &item.notes);
+
+                buffer.push_raw("<content type=\"html\">");
+                buffer.push_text(content.as_str());
+                buffer.push_raw("</content>");
+            }
+
+            buffer.push_raw("</entry>");
+        }
+    }
+
+    fn write_search_items(&mut self, _: &SearchQuery, items: Vec<FeedItem>) {
+        self.write_feed_items(items);
+    }
+
+    fn write_pagination(&mut self, pagination: &Pagination) {
+        let links = [
+            ("first", Some(&pagination.first)),
+            ("previous", pagination.previous.as_ref()),
+            ("next", pagination.next.as_ref()),
+            ("last", pagination.last.as_ref()),
+        ];
+
+        let buffer = &mut self.buffer;
+
+        for (rel, query) in links {
+            let Some(url) = query.and_then(|query| page_link(&self.link, &self.path, query)) else {
+                continue;
+            };
+
+            buffer.push_raw("<link rel=\"");
+            buffer.push_raw(rel);
+            buffer.push_raw("\" type=\"application/atom+xml\" href=\"");
+            buffer.push_url(url.as_str());
+            buffer.push_raw("\"/>");
+        }
+    }
+
+    fn finish(mut self) -> String {
+        self.buffer.push_raw("</feed>");
+
+        self.buffer.into_string()
+    }
+}
+
+/// Formats as RFC 3339 in UTC, e.g. `2025-01-31T12:00:00Z`
+pub fn fmt_rfc3339(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0);
+
+    let days = (secs / 86_400) as i64;
+    let secs_of_day = secs % 86_400;
+
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        secs_of_day / 3600,
+        secs_of_day % 3600 / 60,
+        secs_of_day % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::DocumentKind;
+    use crate::FeedItem;
+    use crate::FeedWriter;
+    use crate::atom::AtomWriter;
+    use crate::atom::fmt_rfc3339;
+    use std::time::Duration;
+    use std::time::SystemTime;
+
+    #[test]
+    fn rfc3339_dates() {
+        assert_eq!(fmt_rfc3339(SystemTime::UNIX_EPOCH), "1970-01-01T00:00:00Z");
+        assert_eq!(
+            fmt_rfc3339(SystemTime::UNIX_EPOCH + Duration::from_secs(951_782_400 + 3_723)),
+            "2000-02-29T01:02:03Z"
+        );
+        assert_eq!(
+            fmt_rfc3339(SystemTime::UNIX_EPOCH + Duration::from_secs(1_767_225_599)),
+            "2025-12-31T23:59:59Z"
+        );
+    }
+
+    #[test]
+    fn entries_are_escaped() {
+        let mut writer = AtomWriter::new(
+            "Me & you",
+            "<links>",
+            "https://paket.example/",
+            SystemTime::UNIX_EPOCH,
+        );
+        writer.write_feed_items(vec![FeedItem {
+            title: "AT&T <3 you".to_string(),
+            link: "https://example.com/?a=1&b=\"2\"".to_string(),
+            pub_date: String::new(),
+            expire_date: String::new(),
+            pub_time: SystemTime::UNIX_EPOCH,
+            guid: "guid".to_string(),
+            kind: DocumentKind::Html,
+            tags: Vec::new(),
+            notes: Vec::new(),
+        }]);
+        let feed = writer.finish();
+
+        assert!(feed.contains("<title type=\"text\">Me &amp; you</title>"));
+        assert!(feed.contains("<subtitle type=\"text\">&lt;links&gt;</subtitle>"));
+        assert!(feed.contains("<link rel=\"self\" type=\"application/atom+xml\" href=\"https://paket.example/feed.atom\"/>"));
+        assert!(feed.contains("<title type=\"text\">AT&amp;T &lt;3 you</title>"));
+        assert!(feed.contains("href=\"https://example.com/?a=1&amp;b=&quot;2&quot;\""));
+        assert!(feed.ends_with("</entry></feed>"));
+    }
+}
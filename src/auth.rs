@@ -0,0 +1,844 @@
+use crate::App;
+use crate::filter::FeedFilter;
+use crate::user;
+use crate::user::DEFAULT_USER_ID;
+use crate::user::User;
+use anyhow::anyhow;
+use anyhow::bail;
+use argh::FromArgs;
+use axum::body::Body;
+use axum::extract::Request;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::http::header::AUTHORIZATION;
+use axum::http::header::WWW_AUTHENTICATE;
+use axum::middleware::Next;
+use axum::response::Response;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use duckdb::Connection;
+use duckdb::params;
+use log::error;
+use ring::digest::SHA256;
+use ring::digest::digest;
+use ring::hmac;
+use ring::rand::SecureRandom;
+use ring::rand::SystemRandom;
+use std::fmt;
+use url::Url;
+use url::form_urlencoded;
+
+const TOKEN_PREFIX: &str = "paket_";
+const TOKEN_BYTES: usize = 32;
+
+/// Which requests need a token or basic credentials
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthMode {
+    None,
+    /// Saving, deleting and editing articles. Feeds and pages stay public
+    Writes,
+    /// Reading feeds, pages and the API as well
+    All,
+}
+
+pub fn parse_auth_mode(mode: &str) -> Result<AuthMode, String> {
+    match mode {
+        "none" => Ok(AuthMode::None),
+        "writes" => Ok(AuthMode::Writes),
+        "all" => Ok(AuthMode::All),
+        _ => Err(format!("unknown auth {mode}")),
+    }
+}
+
+/// Marks requests carrying a valid `access_token` of their user in the query, as
+/// sent by bookmarklets
+#[derive(Debug, Clone, Copy)]
+pub struct QueryToken;
+
+/// `user:password` accepted through HTTP Basic auth, so browsers can use the HTML pages
+#[derive(Clone)]
+pub struct BasicAuth {
+    user: String,
+    password: String,
+}
+
+impl fmt::Debug for BasicAuth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BasicAuth")
+            .field("user", &self.user)
+            .finish_non_exhaustive()
+    }
+}
+
+pub fn parse_basic_auth(credentials: &str) -> Result<BasicAuth, String> {
+    match credentials.split_once(':') {
+        Some((user, password)) if !user.is_empty() && !password.is_empty() => Ok(BasicAuth {
+            user: user.to_string(),
+            password: password.to_string(),
+        }),
+        _ => Err(String::from("expected user:password")),
+    }
+}
+
+impl BasicAuth {
+    /// `decoded` is the `user:password` of an `Authorization: Basic` header
+    fn matches(&self, decoded: &str) -> bool {
+        let expected = format!("{}:{}", self.user, self.password);
+        let Ok(key) = hmac::Key::generate(hmac::HMAC_SHA256, &SystemRandom::new()) else {
+            return false;
+        };
+
+        // the tags are compared in constant time, and under a fresh random key how far
+        // they match tells nothing about the password
+        let tag = hmac::sign(&key, expected.as_bytes());
+        hmac::verify(&key, decoded.as_bytes(), tag.as_ref()).is_ok()
+    }
+}
+
+/// `user:password` from the base64 part of an `Authorization: Basic` header
+fn decode_basic(encoded: &str) -> Option<String> {
+    STANDARD
+        .decode(encoded)
+        .ok()
+        .and_then(|decoded| String::from_utf8(decoded).ok())
+}
+
+/// Manage API tokens
+#[derive(Debug, Clone, FromArgs)]
+#[argh(subcommand, name = "token")]
+pub struct TokenCommand {
+    #[argh(subcommand)]
+    action: TokenAction,
+}
+
+#[derive(Debug, Clone, FromArgs)]
+#[argh(subcommand)]
+enum TokenAction {
+    Create(CreateToken),
+    List(ListTokens),
+    Revoke(RevokeToken),
+}
+
+/// Manage secret feed urls for readers which can't send credentials
+#[derive(Debug, Clone, FromArgs)]
+#[argh(subcommand, name = "feed-token")]
+pub struct FeedTokenCommand {
+    #[argh(subcommand)]
+    action: FeedTokenAction,
+}
+
+#[derive(Debug, Clone, FromArgs)]
+#[argh(subcommand)]
+enum FeedTokenAction {
+    Create(CreateFeedToken),
+    List(ListTokens),
+    Revoke(RevokeToken),
+    Regenerate(RegenerateFeedToken),
+}
+
+/// Create a feed url and print it. It can't be shown again
+#[derive(Debug, Clone, FromArgs)]
+#[argh(subcommand, name = "create")]
+struct CreateFeedToken {
+    /// name of the reader using the feed
+    #[argh(positional)]
+    name: String,
+
+    /// articles in the feed: all, pinned or tag:<tag>
+    #[argh(option, default = "FeedScope::All", from_str_fn(parse_feed_scope))]
+    scope: FeedScope,
+
+    /// user whose articles are in the feed
+    #[argh(option, default = "String::from(\"default\")")]
+    user: String,
+}
+
+/// Replace the url of a feed, keeping its scope
+#[derive(Debug, Clone, FromArgs)]
+#[argh(subcommand, name = "regenerate")]
+struct RegenerateFeedToken {
+    /// name of the feed
+    #[argh(positional)]
+    name: String,
+}
+
+/// Articles a feed token gives access to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FeedScope {
+    All,
+    Pinned,
+    Tag(String),
+}
+
+fn parse_feed_scope(scope: &str) -> Result<FeedScope, String> {
+    match scope {
+        "all" => Ok(FeedScope::All),
+        "pinned" => Ok(FeedScope::Pinned),
+        _ => match scope
+            .strip_prefix("tag:")
+            .map(|tag| tag.trim().to_lowercase())
+        {
+            Some(tag) if !tag.is_empty() => Ok(FeedScope::Tag(tag)),
+            _ => Err(format!("unknown scope {scope}")),
+        },
+    }
+}
+
+impl FeedScope {
+    fn as_str(&self) -> &'static str {
+        match self {
+            FeedScope::All => "all",
+            FeedScope::Pinned => "pinned",
+            FeedScope::Tag(_) => "tag",
+        }
+    }
+
+    fn from_row(scope: &str, tag: Option<String>) -> Self {
+        match (scope, tag) {
+            ("pinned", _) => FeedScope::Pinned,
+            ("tag", Some(tag)) => FeedScope::Tag(tag),
+            _ => FeedScope::All,
+        }
+    }
+
+    /// The reader's filter within the scope
+    pub fn narrow(&self, filter: FeedFilter) -> FeedFilter {
+        match self {
+            FeedScope::All => filter,
+            FeedScope::Pinned => FeedFilter {
+                pinned: true,
+                ..filter
+            },
+            FeedScope::Tag(tag) => FeedFilter {
+                tag: Some(tag.clone()),
+                ..filter
+            },
+        }
+    }
+}
+
+impl fmt::Display for FeedScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FeedScope::Tag(tag) => write!(f, "tag:{tag}"),
+            scope => f.write_str(scope.as_str()),
+        }
+    }
+}
+
+/// Create a token and print it. It can't be shown again
+#[derive(Debug, Clone, FromArgs)]
+#[argh(subcommand, name = "create")]
+struct CreateToken {
+    /// name of the client using the token
+    #[argh(positional)]
+    name: String,
+
+    /// user the client acts as
+    #[argh(option, default = "String::from(\"default\")")]
+    user: String,
+}
+
+/// List token names and when they were last used
+#[derive(Debug, Clone, FromArgs)]
+#[argh(subcommand, name = "list")]
+struct ListTokens {}
+
+/// Revoke a token by name
+#[derive(Debug, Clone, FromArgs)]
+#[argh(subcommand, name = "revoke")]
+struct RevokeToken {
+    /// name of the token
+    #[argh(positional)]
+    name: String,
+}
+
+pub fn run_token_command(
+    db_connection: &mut Connection,
+    command: &TokenCommand,
+) -> anyhow::Result<()> {
+    match &command.action {
+        TokenAction::Create(create) => {
+            let user_id = find_user(db_connection, &create.user)?;
+            let token = create_token(db_connection, &create.name, user_id)?;
+            println!("{token}");
+        }
+        TokenAction::List(_) => {
+            for token in list_tokens(db_connection)? {
+                let used = token.used.unwrap_or_else(|| "never".to_string());
+                println!(
+                    "{}\tuser {}\tcreated {}\tused {used}",
+                    token.name, token.user, token.created
+                );
+            }
+        }
+        TokenAction::Revoke(revoke) => {
+            if !revoke_token(db_connection, &revoke.name)? {
+                bail!("no token {}", revoke.name);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+pub fn run_feed_token_command(
+    db_connection: &mut Connection,
+    command: &FeedTokenCommand,
+    link: &str,
+) -> anyhow::Result<()> {
+    match &command.action {
+        FeedTokenAction::Create(create) => {
+            let user_id = find_user(db_connection, &create.user)?;
+            let token = create_feed_token(db_connection, &create.name, &create.scope, user_id)?;
+            println!("{}", feed_url(link, &token)?);
+        }
+        FeedTokenAction::List(_) => {
+            for token in list_feed_tokens(db_connection)? {
+                let used = token.used.unwrap_or_else(|| "never".to_string());
+                println!(
+                    "{}\tuser {}\t{}\tcreated {}\tused {used}",
+                    token.name, token.user, token.scope, token.created
+                );
+            }
+        }
+        FeedTokenAction::Revoke(revoke) => {
+            if !revoke_feed_token(db_connection, &revoke.name)? {
+                bail!("no feed token {}", revoke.name);
+            }
+        }
+        FeedTokenAction::Regenerate(regenerate) => {
+            let Some(token) = regenerate_feed_token(db_connection, &regenerate.name)? else {
+                bail!("no feed token {}", regenerate.name);
+            };
+            println!("{}", feed_url(link, &token)?);
+        }
+    }
+
+    Ok(())
+}
+
+fn find_user(db_connection: &mut Connection, name: &str) -> anyhow::Result<i64> {
+    match user::find_user_id(db_connection, name)? {
+        Some(id) => Ok(id),
+        None => bail!("no user {name}"),
+    }
+}
+
+/// RSS url of the feed token. Atom and JSON Feed are served under the same token
+fn feed_url(link: &str, token: &str) -> anyhow::Result<Url> {
+    Ok(Url::parse(link)?.join(&format!("feed/{token}.xml"))?)
+}
+
+fn random_token() -> anyhow::Result<String> {
+    let mut bytes = [0; TOKEN_BYTES];
+    SystemRandom::new()
+        .fill(&mut bytes)
+        .map_err(|_| anyhow!("no randomness"))?;
+    Ok(format!("{TOKEN_PREFIX}{}", hex(&bytes)))
+}
+
+/// Stores the hash of a new random token of the user under the name,
+/// replacing the user's token of the same name
+pub fn create_token(
+    db_connection: &mut Connection,
+    name: &str,
+    user_id: i64,
+) -> anyhow::Result<String> {
+    let token = random_token()?;
+
+    let tx = db_connection.transaction()?;
+    let deleted = tx.execute(
+        "DELETE FROM tokens WHERE name = ? AND user_id = ?",
+        params![name, user_id],
+    )?;
+    if deleted == 0
+        && tx.query_row(
+            "SELECT count(*) FROM tokens WHERE name = ?",
+            [name],
+            |row| row.get::<_, i64>(0),
+        )? > 0
+    {
+        bail!("token {name} belongs to another user");
+    }
+    tx.execute(
+        "INSERT INTO tokens (name, hash, user_id, created_at) VALUES (?, ?, ?, current_timestamp)",
+        params![name, hash_token(&token), user_id],
+    )?;
+    tx.commit()?;
+
+    Ok(token)
+}
+
+struct Token {
+    name: String,
+    user: String,
+    created: String,
+    used: Option<String>,
+}
+
+/// Tokens with their user and the creation and last use dates, oldest first
+fn list_tokens(db_connection: &mut Connection) -> anyhow::Result<Vec<Token>> {
+    let mut select_stmt = db_connection.prepare(
+        "SELECT
+        tokens.name,
+        users.name,
+        strftime(CAST(tokens.created_at AS TIMESTAMP), '%Y-%m-%d %X'),
+        strftime(CAST(tokens.used_at AS TIMESTAMP), '%Y-%m-%d %X')
+        FROM tokens
+        JOIN users ON users.id = tokens.user_id
+        ORDER BY tokens.created_at ASC",
+    )?;
+
+    let tokens = select_stmt
+        .query_map([], |row| {
+            Ok(Token {
+                name: row.get(0)?,
+                user: row.get(1)?,
+                created: row.get(2)?,
+                used: row.get(3)?,
+            })
+        })?
+        .collect::<Result<_, _>>()?;
+
+    Ok(tokens)
+}
+
+fn revoke_token(db_connection: &mut Connection, name: &str) -> anyhow::Result<bool> {
+    let revoked = db_connection.execute("DELETE FROM tokens WHERE name = ?", [name])?;
+    Ok(revoked > 0)
+}
+
+/// User of a known token, recording its use
+fn verify_token(db_connection: &mut Connection, token: &str) -> anyhow::Result<Option<i64>> {
+    let mut update_stmt = db_connection.prepare(
+        "UPDATE tokens SET used_at = current_timestamp
+        WHERE hash = ?
+        RETURNING user_id",
+    )?;
+
+    let mut rows = update_stmt.query([hash_token(token)])?;
+    let Some(row) = rows.next()? else {
+        return Ok(None);
+    };
+
+    Ok(Some(row.get(0)?))
+}
+
+/// Stores the hash of a new random feed token of the user under the name,
+/// replacing the user's feed of the same name
+fn create_feed_token(
+    db_connection: &mut Connection,
+    name: &str,
+    scope: &FeedScope,
+    user_id: i64,
+) -> anyhow::Result<String> {
+    let token = random_token()?;
+    let tag = match scope {
+        FeedScope::Tag(tag) => Some(tag.as_str()),
+        _ => None,
+    };
+
+    let tx = db_connection.transaction()?;
+    let deleted = tx.execute(
+        "DELETE FROM feed_tokens WHERE name = ? AND user_id = ?",
+        params![name, user_id],
+    )?;
+    if deleted == 0
+        && tx.query_row(
+            "SELECT count(*) FROM feed_tokens WHERE name = ?",
+            [name],
+            |row| row.get::<_, i64>(0),
+        )? > 0
+    {
+        bail!("feed token {name} belongs to another user");
+    }
+    tx.execute(
+        "INSERT INTO feed_tokens (name, hash, scope, tag, user_id, created_at)
+        VALUES (?, ?, ?, ?, ?, current_timestamp)",
+        params![name, hash_token(&token), scope.as_str(), tag, user_id],
+    )?;
+    tx.commit()?;
+
+    Ok(token)
+}
+
+struct FeedToken {
+    name: String,
+    user: String,
+    scope: FeedScope,
+    created: String,
+    used: Option<String>,
+}
+
+/// Feed tokens with their user and the creation and last use dates, oldest first
+fn list_feed_tokens(db_connection: &mut Connection) -> anyhow::Result<Vec<FeedToken>> {
+    let mut select_stmt = db_connection.prepare(
+        "SELECT
+        feed_tokens.name,
+        users.name,
+        feed_tokens.scope,
+        feed_tokens.tag,
+        strftime(CAST(feed_tokens.created_at AS TIMESTAMP), '%Y-%m-%d %X'),
+        strftime(CAST(feed_tokens.used_at AS TIMESTAMP), '%Y-%m-%d %X')
+        FROM feed_tokens
+        JOIN users ON users.id = feed_tokens.user_id
+        ORDER BY feed_tokens.created_at ASC",
+    )?;
+
+    let tokens = select_stmt
+        .query_map([], |row| {
+            let scope: String = row.get(2)?;
+            Ok(FeedToken {
+                name: row.get(0)?,
+                user: row.get(1)?,
+                scope: FeedScope::from_row(&scope, row.get(3)?),
+                created: row.get(4)?,
+                used: row.get(5)?,
+            })
+        })?
+        .collect::<Result<_, _>>()?;
+
+    Ok(tokens)
+}
+
+fn revoke_feed_token(db_connection: &mut Connection, name: &str) -> anyhow::Result<bool> {
+    let revoked = db_connection.execute("DELETE FROM feed_tokens WHERE name = ?", [name])?;
+    Ok(revoked > 0)
+}
+
+/// New token for a known feed. The old url stops working
+fn regenerate_feed_token(
+    db_connection: &mut Connection,
+    name: &str,
+) -> anyhow::Result<Option<String>> {
+    let token = random_token()?;
+
+    let regenerated = db_connection.execute(
+        "UPDATE feed_tokens SET
+        hash = ?,
+        created_at = current_timestamp,
+        used_at = NULL
+        WHERE name = ?",
+        params![hash_token(&token), name],
+    )?;
+
+    Ok((regenerated > 0).then_some(token))
+}
+
+/// User and scope of a known feed token, recording its use
+pub fn verify_feed_token(
+    db_connection: &mut Connection,
+    token: &str,
+) -> anyhow::Result<Option<(i64, FeedScope)>> {
+    let mut update_stmt = db_connection.prepare(
+        "UPDATE feed_tokens SET used_at = current_timestamp
+        WHERE hash = ?
+        RETURNING user_id, scope, tag",
+    )?;
+
+    let mut rows = update_stmt.query([hash_token(token)])?;
+    let Some(row) = rows.next()? else {
+        return Ok(None);
+    };
+    let scope: String = row.get(1)?;
+
+    Ok(Some((
+        row.get(0)?,
+        FeedScope::from_row(&scope, row.get(2)?),
+    )))
+}
+
+fn hash_token(token: &str) -> String {
+    hex(digest(&SHA256, token.as_bytes()).as_ref())
+}
+
+pub fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+pub fn unhex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// Guards routes which change articles, and the feeds at well-known urls. Once auth
+/// is on, feed readers without credentials subscribe through feed tokens instead
+pub async fn require_user(State(state): State<App>, request: Request, next: Next) -> Response {
+    let is_required = state.args.auth != AuthMode::None;
+    run_as_user(&state, request, next, is_required).await
+}
+
+/// Guards routes which only read articles
+pub async fn require_reader(State(state): State<App>, request: Request, next: Next) -> Response {
+    let is_required = state.args.auth == AuthMode::All;
+    run_as_user(&state, request, next, is_required).await
+}
+
+/// Hands the user of the credentials, if any, to routes which decide themselves whom
+/// to let in. Without auth, requests without credentials act as the default user
+pub async fn identify_user(State(state): State<App>, mut request: Request, next: Next) -> Response {
+    let user = {
+        let mut db_lock = state.db_connection.lock().unwrap();
+
+        authenticate(&state, &mut db_lock, &request).and_then(|user| match user {
+            Some(user) => Ok(Some(user)),
+            None if state.args.auth != AuthMode::None => Ok(None),
+            None => user::fetch_user(&mut db_lock, DEFAULT_USER_ID, &state.args),
+        })
+    };
+
+    match user {
+        Ok(user) => {
+            request.extensions_mut().insert(user);
+            next.run(request).await
+        }
+        Err(err) => {
+            error!("{err}");
+            Response::builder()
+                .status(StatusCode::INTERNAL_SERVER_ERROR)
+                .body(Body::empty())
+                .unwrap()
+        }
+    }
+}
+
+/// Hands the user of the credentials to the handler. Requests without valid credentials
+/// act as the default user unless credentials are required
+async fn run_as_user(state: &App, mut request: Request, next: Next, is_required: bool) -> Response {
+    let user = {
+        let mut db_lock = state.db_connection.lock().unwrap();
+
+        authenticate(state, &mut db_lock, &request).and_then(|user| match user {
+            Some(user) => {
+                // browsers send cached basic credentials along with the bookmarklet's token
+                let by_query_token = match query_token(request.uri().query()) {
+                    Some(token) => verify_token(&mut db_lock, &token)? == Some(user.id),
+                    None => false,
+                };
+                Ok(Some((user, by_query_token)))
+            }
+            None if is_required => Ok(None),
+            None => Ok(
+                user::fetch_user(&mut db_lock, DEFAULT_USER_ID, &state.args)?
+                    .map(|user| (user, false)),
+            ),
+        })
+    };
+
+    match user {
+        Ok(Some((user, by_query_token))) => {
+            request.extensions_mut().insert(user);
+            if by_query_token {
+                request.extensions_mut().insert(QueryToken);
+            }
+            next.run(request).await
+        }
+        Ok(None) => unauthorized(),
+        Err(err) => {
+            error!("{err}");
+            Response::builder()
+                .status(StatusCode::INTERNAL_SERVER_ERROR)
+                .body(Body::empty())
+                .unwrap()
+        }
+    }
+}
+
+/// The user of a known bearer token. Basic credentials are either `--basic-auth`,
+/// for the default user, or a user's name with one of their tokens as password.
+/// Bookmarklets can't send headers, they pass the token as `access_token` in the query
+fn authenticate(
+    state: &App,
+    db_connection: &mut Connection,
+    request: &Request,
+) -> anyhow::Result<Option<User>> {
+    let Some((scheme, credentials)) = request
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split_once(' '))
+    else {
+        return match query_token(request.uri().query()) {
+            Some(token) => match verify_token(db_connection, &token)? {
+                Some(user_id) => user::fetch_user(db_connection, user_id, &state.args),
+                None => Ok(None),
+            },
+            None => Ok(None),
+        };
+    };
+    let credentials = credentials.trim();
+
+    if scheme.eq_ignore_ascii_case("bearer") {
+        return match verify_token(db_connection, credentials)? {
+            Some(user_id) => user::fetch_user(db_connection, user_id, &state.args),
+            None => Ok(None),
+        };
+    }
+
+    if !scheme.eq_ignore_ascii_case("basic") {
+        return Ok(None);
+    }
+    let Some(decoded) = decode_basic(credentials) else {
+        return Ok(None);
+    };
+
+    if let Some(basic_auth) = &state.args.basic_auth
+        && basic_auth.matches(&decoded)
+    {
+        return user::fetch_user(db_connection, DEFAULT_USER_ID, &state.args);
+    }
+
+    let Some((name, token)) = decoded.split_once(':') else {
+        return Ok(None);
+    };
+    let Some(user_id) = verify_token(db_connection, token)? else {
+        return Ok(None);
+    };
+
+    let user = user::fetch_user(db_connection, user_id, &state.args)?;
+    Ok(user.filter(|user| user.name == name))
+}
+
+/// Bearer token in the query, as in RFC 6750
+fn query_token(query: Option<&str>) -> Option<String> {
+    form_urlencoded::parse(query?.as_bytes())
+        .find(|(name, _)| name == "access_token")
+        .map(|(_, token)| token.into_owned())
+}
+
+/// Browsers ask for basic credentials, `--basic-auth` or a user's name and token
+pub fn unauthorized() -> Response {
+    Response::builder()
+        .status(StatusCode::UNAUTHORIZED)
+        .header(WWW_AUTHENTICATE, "Bearer realm=\"Paket\"")
+        .header(WWW_AUTHENTICATE, "Basic realm=\"Paket\", charset=\"UTF-8\"")
+        .body(Body::empty())
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::auth::FeedScope;
+    use crate::auth::create_feed_token;
+    use crate::auth::create_token;
+    use crate::auth::decode_basic;
+    use crate::auth::list_feed_tokens;
+    use crate::auth::list_tokens;
+    use crate::auth::parse_basic_auth;
+    use crate::auth::parse_feed_scope;
+    use crate::auth::regenerate_feed_token;
+    use crate::auth::revoke_feed_token;
+    use crate::auth::revoke_token;
+    use crate::auth::verify_feed_token;
+    use crate::auth::verify_token;
+    use crate::filter::FeedFilter;
+    use crate::setup_tables;
+    use duckdb::Connection;
+
+    #[test]
+    fn tokens_are_stored_hashed() {
+        let mut db_connection = Connection::open_in_memory().unwrap();
+        setup_tables(&mut db_connection).unwrap();
+
+        let token = create_token(&mut db_connection, "firefox", 0).unwrap();
+        assert!(token.starts_with("paket_"));
+        assert_eq!(verify_token(&mut db_connection, &token).unwrap(), Some(0));
+        assert_eq!(
+            verify_token(&mut db_connection, "paket_unknown").unwrap(),
+            None
+        );
+        assert!(create_token(&mut db_connection, "firefox", 1).is_err());
+
+        let stored = db_connection
+            .query_row("SELECT hash FROM tokens", [], |row| row.get::<_, String>(0))
+            .unwrap();
+        assert_ne!(stored, token);
+
+        let tokens = list_tokens(&mut db_connection).unwrap();
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(tokens[0].user, "default");
+        assert!(tokens[0].used.is_some());
+
+        assert!(revoke_token(&mut db_connection, "firefox").unwrap());
+        assert!(!revoke_token(&mut db_connection, "firefox").unwrap());
+        assert_eq!(verify_token(&mut db_connection, &token).unwrap(), None);
+    }
+
+    #[test]
+    fn feed_tokens_keep_scope_when_regenerated() {
+        let mut db_connection = Connection::open_in_memory().unwrap();
+        setup_tables(&mut db_connection).unwrap();
+
+        let scope = parse_feed_scope("tag: Rust").unwrap();
+        assert_eq!(scope, FeedScope::Tag("rust".to_string()));
+
+        let token = create_feed_token(&mut db_connection, "reader", &scope, 0).unwrap();
+        assert_eq!(
+            verify_feed_token(&mut db_connection, &token).unwrap(),
+            Some((0, scope.clone()))
+        );
+
+        let regenerated = regenerate_feed_token(&mut db_connection, "reader")
+            .unwrap()
+            .unwrap();
+        assert_eq!(verify_feed_token(&mut db_connection, &token).unwrap(), None);
+        assert_eq!(
+            verify_feed_token(&mut db_connection, &regenerated).unwrap(),
+            Some((0, scope))
+        );
+        assert_eq!(
+            regenerate_feed_token(&mut db_connection, "unknown").unwrap(),
+            None
+        );
+
+        let tokens = list_feed_tokens(&mut db_connection).unwrap();
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(tokens[0].scope.to_string(), "tag:rust");
+
+        assert!(revoke_feed_token(&mut db_connection, "reader").unwrap());
+        assert_eq!(
+            verify_feed_token(&mut db_connection, &regenerated).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn feed_scopes_narrow_filter() {
+        let filter = FeedFilter {
+            tag: Some("web".to_string()),
+            ..FeedFilter::default()
+        };
+
+        assert_eq!(FeedScope::All.narrow(filter.clone()), filter);
+        assert!(FeedScope::Pinned.narrow(filter.clone()).pinned);
+        assert_eq!(
+            FeedScope::Tag("rust".to_string()).narrow(filter).tag(),
+            Some("rust".to_string())
+        );
+
+        assert_eq!(parse_feed_scope("pinned"), Ok(FeedScope::Pinned));
+        assert!(parse_feed_scope("tag:").is_err());
+        assert!(parse_feed_scope("starred").is_err());
+    }
+
+    #[test]
+    fn basic_credentials() {
+        let basic_auth = parse_basic_auth("reader:secret:with:colons").unwrap();
+        let decoded = decode_basic("cmVhZGVyOnNlY3JldDp3aXRoOmNvbG9ucw==").unwrap();
+        assert_eq!(decoded, "reader:secret:with:colons");
+        assert!(basic_auth.matches(&decoded));
+        assert!(!basic_auth.matches("reader:secret"));
+        assert_eq!(decode_basic("not base64"), None);
+        assert!(!format!("{basic_auth:?}").contains("secret"));
+
+        assert!(parse_basic_auth("reader").is_err());
+        assert!(parse_basic_auth(":secret").is_err());
+    }
+}
@@ -0,0 +1,270 @@
+use crate::filter::FeedFilter;
+use crate::page::Page;
+use axum::http::HeaderMap;
+use httpdate::fmt_http_date;
+use httpdate::parse_http_date;
+use std::collections::HashMap;
+use std::time::Duration;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+/// How many feeds are kept, every page and filter being one
+const MAX_FEEDS: usize = 64;
+
+/// Finished feeds per user, path, page and filter, valid as long as the articles don't change
+#[derive(Default)]
+pub struct FeedCache {
+    feeds: HashMap<(i64, String, Page, FeedFilter), CachedFeed>,
+    /// Counts lookups, to tell which feed was used least recently
+    clock: u64,
+}
+
+struct CachedFeed {
+    revision: i64,
+    body: String,
+    last_used: u64,
+}
+
+impl FeedCache {
+    pub fn get(
+        &mut self,
+        user_id: i64,
+        path: &str,
+        page: &Page,
+        filter: &FeedFilter,
+        revision: i64,
+    ) -> Option<String> {
+        self.clock += 1;
+
+        let feed = self
+            .feeds
+            .get_mut(&(user_id, path.to_string(), *page, filter.clone()))
+            .filter(|feed| feed.revision == revision)?;
+        feed.last_used = self.clock;
+        Some(feed.body.clone())
+    }
+
+    /// Feeds of older revisions are dropped, they can't be served anymore. Once
+    /// full, the least recently used feed makes room
+    pub fn insert(
+        &mut self,
+        user_id: i64,
+        path: &str,
+        page: Page,
+        filter: FeedFilter,
+        revision: i64,
+        body: String,
+    ) {
+        self.feeds.retain(|_, feed| feed.revision == revision);
+
+        let key = (user_id, path.to_string(), page, filter);
+        if self.feeds.len() >= MAX_FEEDS && !self.feeds.contains_key(&key) {
+            let least_used = self
+                .feeds
+                .iter()
+                .min_by_key(|(_, feed)| feed.last_used)
+                .map(|(key, _)| key.clone());
+            if let Some(least_used) = least_used {
+                self.feeds.remove(&least_used);
+            }
+        }
+
+        self.clock += 1;
+        self.feeds.insert(
+            key,
+            CachedFeed {
+                revision,
+                body,
+                last_used: self.clock,
+            },
+        );
+    }
+}
+
+/// `ETag` and `Last-Modified` of a feed, `revision` being the time of the last change in microseconds
+pub struct Validators {
+    pub etag: String,
+    pub last_modified: SystemTime,
+}
+
+impl Validators {
+    pub fn new(content_type: &str, revision: i64) -> Self {
+        let format = content_type.rsplit('/').next().unwrap_or(content_type);
+
+        Self {
+            etag: format!("\"{format}-{revision:x}\""),
+            last_modified: UNIX_EPOCH + Duration::from_micros(revision.max(0) as u64),
+        }
+    }
+
+    pub fn last_modified_header(&self) -> String {
+        fmt_http_date(self.last_modified)
+    }
+
+    /// Whether the client's copy is still current. `If-None-Match` wins over `If-Modified-Since`
+    pub fn is_fresh(&self, headers: &HeaderMap) -> bool {
+        if let Some(if_none_match) = headers.get("If-None-Match") {
+            let Ok(if_none_match) = if_none_match.to_str() else {
+                return false;
+            };
+
+            return if_none_match
+                .split(',')
+                .map(str::trim)
+                .any(|etag| etag == "*" || etag.strip_prefix("W/").unwrap_or(etag) == self.etag);
+        }
+
+        let if_modified_since = headers
+            .get("If-Modified-Since")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| parse_http_date(value).ok());
+
+        match if_modified_since {
+            // HTTP dates have no sub-second precision
+            Some(since) => since >= truncate_to_secs(self.last_modified),
+            None => false,
+        }
+    }
+}
+
+fn truncate_to_secs(time: SystemTime) -> SystemTime {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0);
+
+    UNIX_EPOCH + Duration::from_secs(secs)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cache::FeedCache;
+    use crate::cache::MAX_FEEDS;
+    use crate::cache::Validators;
+    use crate::filter::FeedFilter;
+    use crate::page::Page;
+    use crate::page::PageQuery;
+    use axum::http::HeaderMap;
+    use axum::http::HeaderValue;
+
+    #[test]
+    fn conditional_requests() {
+        let validators = Validators::new("application/rss+xml", 1_767_225_599_500_000);
+        assert_eq!(validators.etag, "\"rss+xml-6474846189ee0\"");
+        assert_eq!(
+            validators.last_modified_header(),
+            "Wed, 31 Dec 2025 23:59:59 GMT"
+        );
+
+        let mut headers = HeaderMap::new();
+        assert!(!validators.is_fresh(&headers));
+
+        headers.insert(
+            "If-Modified-Since",
+            HeaderValue::from_static("Wed, 31 Dec 2025 23:59:59 GMT"),
+        );
+        assert!(validators.is_fresh(&headers));
+
+        headers.insert(
+            "If-Modified-Since",
+            HeaderValue::from_static("Wed, 31 Dec 2025 23:59:58 GMT"),
+        );
+        assert!(!validators.is_fresh(&headers));
+
+        headers.insert(
+            "If-None-Match",
+            HeaderValue::from_static("\"atom+xml-6474846189ee0\", W/\"rss+xml-6474846189ee0\""),
+        );
+        assert!(validators.is_fresh(&headers));
+
+        headers.insert("If-None-Match", HeaderValue::from_static("\"rss+xml-1\""));
+        headers.insert(
+            "If-Modified-Since",
+            HeaderValue::from_static("Thu, 01 Jan 2026 00:00:00 GMT"),
+        );
+        assert!(!validators.is_fresh(&headers));
+    }
+
+    #[test]
+    fn cache_is_bound_to_revision() {
+        let page = Page::new(&PageQuery::default(), 100);
+        let other_page = Page::new(&PageQuery::default(), 10);
+
+        let no_filter = FeedFilter::default();
+        let filter = FeedFilter {
+            tag: Some("rust".to_string()),
+            ..FeedFilter::default()
+        };
+
+        let mut cache = FeedCache::default();
+        cache.insert(
+            0,
+            "feed.xml",
+            page,
+            FeedFilter::default(),
+            1,
+            "feed".to_string(),
+        );
+
+        assert_eq!(
+            cache.get(0, "feed.xml", &page, &no_filter, 1),
+            Some("feed".to_string())
+        );
+        assert_eq!(cache.get(0, "feed.xml", &page, &no_filter, 2), None);
+        assert_eq!(cache.get(0, "feed.xml", &other_page, &no_filter, 1), None);
+        assert_eq!(cache.get(0, "feed.atom", &page, &no_filter, 1), None);
+        assert_eq!(cache.get(0, "feed.xml", &page, &filter, 1), None);
+        assert_eq!(cache.get(1, "feed.xml", &page, &no_filter, 1), None);
+
+        cache.insert(
+            0,
+            "feed.atom",
+            page,
+            FeedFilter::default(),
+            2,
+            "feed".to_string(),
+        );
+        assert_eq!(cache.get(0, "feed.xml", &page, &no_filter, 1), None);
+    }
+
+    #[test]
+    fn least_used_feeds_make_room() {
+        let page = |limit| Page::new(&PageQuery::default(), limit);
+        let no_filter = FeedFilter::default();
+
+        let mut cache = FeedCache::default();
+        for limit in 1..=MAX_FEEDS as u32 {
+            cache.insert(
+                0,
+                "feed.xml",
+                page(limit),
+                no_filter.clone(),
+                1,
+                limit.to_string(),
+            );
+        }
+        assert_eq!(
+            cache.get(0, "feed.xml", &page(1), &no_filter, 1),
+            Some("1".to_string())
+        );
+
+        cache.insert(
+            0,
+            "feed.atom",
+            page(1),
+            no_filter.clone(),
+            1,
+            "atom".to_string(),
+        );
+        assert_eq!(cache.feeds.len(), MAX_FEEDS);
+        assert_eq!(
+            cache.get(0, "feed.xml", &page(1), &no_filter, 1),
+            Some("1".to_string())
+        );
+        assert_eq!(cache.get(0, "feed.xml", &page(2), &no_filter, 1), None);
+        assert_eq!(
+            cache.get(0, "feed.atom", &page(1), &no_filter, 1),
+            Some("atom".to_string())
+        );
+    }
+}
@@ -0,0 +1,802 @@
+use crate::App;
+use crate::Args;
+use crate::Article;
+use crate::DbConnection;
+use crate::DocumentKind;
+use crate::FeedWriter;
+use crate::FetchError;
+use crate::auth;
+use crate::fetch_article;
+use crate::html::HtmlWriter;
+use crate::parse_http_url;
+use crate::rss::RssWriter;
+use crate::user;
+use crate::user::User;
+use anyhow::anyhow;
+use anyhow::bail;
+use argh::FromArgs;
+use axum::Extension;
+use axum::extract::Path;
+use axum::extract::State;
+use axum::http::response::Response;
+use axum::http::status::StatusCode;
+use duckdb::Connection;
+use duckdb::OptionalExt;
+use duckdb::params;
+use log::error;
+use log::info;
+use std::time::SystemTime;
+use uuid::Uuid;
+
+/// Named list several users save into, optionally published read-only
+#[derive(Debug, Clone)]
+pub struct Collection {
+    pub id: i64,
+    pub name: String,
+    pub title: String,
+    pub description: String,
+    /// Base url of the collection's pages, ending with a slash
+    pub link: String,
+    /// Time to live in days
+    pub ttl: u32,
+    pub public: bool,
+}
+
+impl Collection {
+    /// Guid of the collection's article at the url, apart from the members' own guids
+    pub fn guid(&self, url: &str) -> String {
+        let namespace = Uuid::new_v5(
+            &Uuid::NAMESPACE_URL,
+            format!("paket:collection:{}", self.id).as_bytes(),
+        );
+        Uuid::new_v5(&namespace, url.as_bytes()).to_string()
+    }
+}
+
+pub struct CollectionItem {
+    pub title: String,
+    pub link: String,
+    pub guid: String,
+    pub pub_date: String,
+    pub expire_date: String,
+    pub kind: DocumentKind,
+    /// Name of the member who saved it, empty once the member is deleted
+    pub saved_by: String,
+}
+
+/// Manage shared collections and their members
+#[derive(Debug, Clone, FromArgs)]
+#[argh(subcommand, name = "collection")]
+pub struct CollectionCommand {
+    #[argh(subcommand)]
+    action: CollectionAction,
+}
+
+#[derive(Debug, Clone, FromArgs)]
+#[argh(subcommand)]
+enum CollectionAction {
+    Create(CreateCollection),
+    List(ListCollections),
+    Update(UpdateCollection),
+    Delete(DeleteCollection),
+    AddMember(AddMember),
+    RemoveMember(RemoveMember),
+}
+
+/// Create a collection. Settings left out follow the global options
+#[derive(Debug, Clone, FromArgs)]
+#[argh(subcommand, name = "create")]
+struct CreateCollection {
+    /// name of the collection, used in its urls
+    #[argh(positional)]
+    name: String,
+
+    /// feed name
+    #[argh(option)]
+    title: Option<String>,
+
+    /// feed description
+    #[argh(option)]
+    desc: Option<String>,
+
+    /// base HTTP url of the collection's pages
+    #[argh(option, from_str_fn(parse_http_url))]
+    link: Option<String>,
+
+    /// time to live in days
+    #[argh(option)]
+    ttl: Option<u32>,
+
+    /// publish the collection as a read-only page and feed
+    #[argh(switch)]
+    public: bool,
+}
+
+/// List collections, their settings and members
+#[derive(Debug, Clone, FromArgs)]
+#[argh(subcommand, name = "list")]
+struct ListCollections {}
+
+/// Change the settings of a collection. Settings left out stay as they are
+#[derive(Debug, Clone, FromArgs)]
+#[argh(subcommand, name = "update")]
+struct UpdateCollection {
+    /// name of the collection
+    #[argh(positional)]
+    name: String,
+
+    /// feed name
+    #[argh(option)]
+    title: Option<String>,
+
+    /// feed description
+    #[argh(option)]
+    desc: Option<String>,
+
+    /// base HTTP url of the collection's pages
+    #[argh(option, from_str_fn(parse_http_url))]
+    link: Option<String>,
+
+    /// time to live in days
+    #[argh(option)]
+    ttl: Option<u32>,
+
+    /// publish the collection, true or false
+    #[argh(option)]
+    public: Option<bool>,
+}
+
+/// Delete a collection with its articles
+#[derive(Debug, Clone, FromArgs)]
+#[argh(subcommand, name = "delete")]
+struct DeleteCollection {
+    /// name of the collection
+    #[argh(positional)]
+    name: String,
+}
+
+/// Let a user save into a collection
+#[derive(Debug, Clone, FromArgs)]
+#[argh(subcommand, name = "add-member")]
+struct AddMember {
+    /// name of the collection
+    #[argh(positional)]
+    name: String,
+
+    /// name of the user
+    #[argh(positional)]
+    user: String,
+}
+
+/// Stop a user from saving into a collection, their articles stay
+#[derive(Debug, Clone, FromArgs)]
+#[argh(subcommand, name = "remove-member")]
+struct RemoveMember {
+    /// name of the collection
+    #[argh(positional)]
+    name: String,
+
+    /// name of the user
+    #[argh(positional)]
+    user: String,
+}
+
+pub fn run_collection_command(
+    db_connection: &mut Connection,
+    command: &CollectionCommand,
+    args: &Args,
+) -> anyhow::Result<()> {
+    match &command.action {
+        CollectionAction::Create(create) => {
+            create_collection(db_connection, create)?;
+        }
+        CollectionAction::List(_) => {
+            for collection in fetch_collections(db_connection, args)? {
+                let members = fetch_members(db_connection, collection.id)?;
+                println!(
+                    "{}\t{}\t{} ({})\tttl {} days\t{}feed.xml\tmembers {}",
+                    collection.name,
+                    if collection.public {
+                        "public"
+                    } else {
+                        "private"
+                    },
+                    collection.title,
+                    collection.description,
+                    collection.ttl,
+                    collection.link,
+                    members.join(", ")
+                );
+            }
+        }
+        CollectionAction::Update(update) => {
+            if !update_collection(db_connection, update)? {
+                bail!("no collection {}", update.name);
+            }
+        }
+        CollectionAction::Delete(delete) => {
+            if !delete_collection(db_connection, &delete.name)? {
+                bail!("no collection {}", delete.name);
+            }
+        }
+        CollectionAction::AddMember(add) => {
+            let (collection_id, user_id) = find_membership(db_connection, &add.name, &add.user)?;
+            db_connection.execute(
+                "INSERT OR IGNORE INTO collection_members (collection_id, user_id)
+                VALUES (?, ?)",
+                params![collection_id, user_id],
+            )?;
+        }
+        CollectionAction::RemoveMember(remove) => {
+            let (collection_id, user_id) =
+                find_membership(db_connection, &remove.name, &remove.user)?;
+            let removed = db_connection.execute(
+                "DELETE FROM collection_members WHERE collection_id = ? AND user_id = ?",
+                params![collection_id, user_id],
+            )?;
+            if removed == 0 {
+                bail!("{} is not a member of {}", remove.user, remove.name);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Collection names end up in urls, so they are kept to letters, digits, `-` and `_`
+fn create_collection(
+    db_connection: &mut Connection,
+    create: &CreateCollection,
+) -> anyhow::Result<i64> {
+    let name = create.name.trim();
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        bail!("invalid collection name {name}");
+    }
+    if find_collection_id(db_connection, name)?.is_some() {
+        bail!("collection {name} exists");
+    }
+
+    let id = db_connection.query_row(
+        "INSERT INTO collections (name, title, description, link, ttl, public, created_at)
+        VALUES (?, ?, ?, ?, ?, ?, current_timestamp)
+        RETURNING id",
+        params![
+            name,
+            create.title,
+            create.desc,
+            create.link,
+            create.ttl,
+            create.public
+        ],
+        |row| row.get(0),
+    )?;
+
+    Ok(id)
+}
+
+fn update_collection(
+    db_connection: &mut Connection,
+    update: &UpdateCollection,
+) -> anyhow::Result<bool> {
+    let updated = db_connection.execute(
+        "UPDATE collections SET
+        title = coalesce(?, title),
+        description = coalesce(?, description),
+        link = coalesce(?, link),
+        ttl = coalesce(?, ttl),
+        public = coalesce(?, public)
+        WHERE name = ?",
+        params![
+            update.title,
+            update.desc,
+            update.link,
+            update.ttl,
+            update.public,
+            update.name
+        ],
+    )?;
+    Ok(updated > 0)
+}
+
+fn delete_collection(db_connection: &mut Connection, name: &str) -> anyhow::Result<bool> {
+    let Some(id) = find_collection_id(db_connection, name)? else {
+        return Ok(false);
+    };
+
+    let tx = db_connection.transaction()?;
+    tx.execute(
+        "DELETE FROM collection_articles WHERE collection_id = ?",
+        [id],
+    )?;
+    tx.execute(
+        "DELETE FROM collection_members WHERE collection_id = ?",
+        [id],
+    )?;
+    tx.execute("DELETE FROM collections WHERE id = ?", [id])?;
+    tx.commit()?;
+
+    Ok(true)
+}
+
+fn find_collection_id(db_connection: &mut Connection, name: &str) -> anyhow::Result<Option<i64>> {
+    let id = db_connection
+        .query_row("SELECT id FROM collections WHERE name = ?", [name], |row| {
+            row.get(0)
+        })
+        .optional()?;
+    Ok(id)
+}
+
+fn find_membership(
+    db_connection: &mut Connection,
+    name: &str,
+    user_name: &str,
+) -> anyhow::Result<(i64, i64)> {
+    let collection_id =
+        find_collection_id(db_connection, name)?.ok_or_else(|| anyhow!("no collection {name}"))?;
+    let user_id = user::find_user_id(db_connection, user_name)?
+        .ok_or_else(|| anyhow!("no user {user_name}"))?;
+    Ok((collection_id, user_id))
+}
+
+/// Names of the collection's members, oldest user first
+fn fetch_members(
+    db_connection: &mut Connection,
+    collection_id: i64,
+) -> anyhow::Result<Vec<String>> {
+    let mut select_stmt = db_connection.prepare(
+        "SELECT users.name FROM collection_members
+        JOIN users ON users.id = collection_members.user_id
+        WHERE collection_members.collection_id = ?
+        ORDER BY users.id ASC",
+    )?;
+
+    let members = select_stmt
+        .query_map([collection_id], |row| row.get(0))?
+        .collect::<Result<_, _>>()?;
+
+    Ok(members)
+}
+
+fn is_member(
+    db_connection: &mut Connection,
+    collection: &Collection,
+    user: &User,
+) -> anyhow::Result<bool> {
+    let is_member = db_connection.query_row(
+        "SELECT EXISTS (
+            SELECT 1 FROM collection_members WHERE collection_id = ? AND user_id = ?)",
+        params![collection.id, user.id],
+        |row| row.get(0),
+    )?;
+    Ok(is_member)
+}
+
+/// The collection with unset settings taken from the global options
+pub fn fetch_collection(
+    db_connection: &mut Connection,
+    name: &str,
+    args: &Args,
+) -> anyhow::Result<Option<Collection>> {
+    let collection = db_connection
+        .query_row(
+            "SELECT id, name, title, description, link, ttl, public FROM collections
+            WHERE name = ?",
+            [name],
+            |row| read_collection(row, args),
+        )
+        .optional()?;
+    Ok(collection)
+}
+
+/// The collection if the user is one of its members. Other users don't get to know it exists
+pub fn fetch_member_collection(
+    db_connection: &mut Connection,
+    name: &str,
+    user: &User,
+    args: &Args,
+) -> anyhow::Result<Option<Collection>> {
+    let Some(collection) = fetch_collection(db_connection, name, args)? else {
+        return Ok(None);
+    };
+
+    if is_member(db_connection, &collection, user)? {
+        Ok(Some(collection))
+    } else {
+        Ok(None)
+    }
+}
+
+/// All collections, oldest first
+fn fetch_collections(
+    db_connection: &mut Connection,
+    args: &Args,
+) -> anyhow::Result<Vec<Collection>> {
+    let mut select_stmt = db_connection.prepare(
+        "SELECT id, name, title, description, link, ttl, public FROM collections
+        ORDER BY id ASC",
+    )?;
+
+    let collections = select_stmt
+        .query_map([], |row| read_collection(row, args))?
+        .collect::<Result<_, _>>()?;
+
+    Ok(collections)
+}
+
+fn read_collection(row: &duckdb::Row, args: &Args) -> duckdb::Result<Collection> {
+    let name: String = row.get(1)?;
+
+    let link = match row.get::<_, Option<String>>(4)? {
+        Some(link) if link.ends_with('/') => link,
+        Some(link) => format!("{link}/"),
+        None => format!("{}collections/{name}/", args.link),
+    };
+
+    Ok(Collection {
+        id: row.get(0)?,
+        title: row
+            .get::<_, Option<String>>(2)?
+            .unwrap_or_else(|| args.name.clone()),
+        description: row
+            .get::<_, Option<String>>(3)?
+            .unwrap_or_else(|| args.desc.clone()),
+        link,
+        ttl: row.get::<_, Option<u32>>(5)?.unwrap_or(args.ttl),
+        public: row.get(6)?,
+        name,
+    })
+}
+
+/// Fetches and stores an article in the collection on behalf of a member, returning its guid.
+/// Failing to fetch is a `FetchError`
+pub async fn add_to_collection(
+    url: &str,
+    title: Option<&str>,
+    collection: &Collection,
+    user: &User,
+    db_connection: DbConnection,
+) -> anyhow::Result<String> {
+    let article = fetch_article(url).await.map_err(FetchError)?.titled(title);
+
+    let mut db_lock = db_connection.lock().unwrap();
+    store_in_collection(&mut db_lock, collection, user, article)
+}
+
+/// Saving a link again moves it to the top and makes the member the one who saved it
+fn store_in_collection(
+    db_connection: &mut Connection,
+    collection: &Collection,
+    user: &User,
+    article: Article,
+) -> anyhow::Result<String> {
+    let guid = collection.guid(article.url.as_str());
+
+    let tx = db_connection.transaction()?;
+    tx.execute(
+        "DELETE FROM collection_articles WHERE collection_id = ? AND (guid = ? OR link = ?)",
+        params![collection.id, &guid, article.url.as_str()],
+    )?;
+    tx.execute(
+        "INSERT INTO collection_articles
+        (collection_id, user_id, title, link, guid, kind, timestamp)
+        VALUES
+        (?, ?, ?, ?, ?, ?, current_timestamp)",
+        params![
+            collection.id,
+            user.id,
+            article.title,
+            article.url.as_str(),
+            &guid,
+            article.kind.as_str()
+        ],
+    )?;
+    tx.commit()?;
+
+    Ok(guid)
+}
+
+/// Any member may remove any of the collection's articles
+pub fn remove_from_collection(
+    db_connection: &mut Connection,
+    collection: &Collection,
+    guid: &str,
+) -> anyhow::Result<bool> {
+    let removed = db_connection.execute(
+        "DELETE FROM collection_articles WHERE collection_id = ? AND guid = ?",
+        params![collection.id, guid],
+    )?;
+    Ok(removed > 0)
+}
+
+/// Deletes articles of every collection whose time to live has ended. Collections
+/// have no trash, nobody owns their articles
+pub fn expire_collection_articles(
+    db_connection: &mut Connection,
+    args: &Args,
+) -> anyhow::Result<()> {
+    db_connection.execute(
+        "DELETE FROM collection_articles
+        WHERE EXISTS (
+            SELECT 1 FROM collections
+            WHERE collections.id = collection_articles.collection_id
+            AND (current_timestamp AT TIME ZONE 'UTC' - collection_articles.timestamp AT TIME ZONE 'UTC')
+                > INTERVAL (coalesce(collections.ttl, ?)) DAY)",
+        [args.ttl],
+    )?;
+    Ok(())
+}
+
+/// The collection's latest articles, newest first
+pub fn fetch_collection_items(
+    db_connection: &mut Connection,
+    collection: &Collection,
+    limit: u32,
+) -> anyhow::Result<Vec<CollectionItem>> {
+    let mut select_stmt = db_connection.prepare(
+        "SELECT
+        collection_articles.title, collection_articles.link, collection_articles.guid,
+        strftime(collection_articles.timestamp AT TIME ZONE 'GMT', '%a, %d %b %Y %X GMT'),
+        strftime((collection_articles.timestamp + INTERVAL (?) DAY) AT TIME ZONE 'GMT', '%a, %d %b %Y %X GMT'),
+        coalesce(users.name, ''),
+        collection_articles.kind
+        FROM collection_articles
+        LEFT JOIN users ON users.id = collection_articles.user_id
+        WHERE collection_articles.collection_id = ?
+        ORDER BY collection_articles.timestamp DESC
+        LIMIT ?",
+    )?;
+
+    let mut rows = select_stmt.query(params![collection.ttl, collection.id, limit])?;
+    let mut items = Vec::new();
+    while let Some(row) = rows.next()? {
+        let kind: String = row.get(6)?;
+        items.push(CollectionItem {
+            title: row.get(0)?,
+            link: row.get(1)?,
+            guid: row.get(2)?,
+            pub_date: row.get(3)?,
+            expire_date: row.get(4)?,
+            kind: kind.parse()?,
+            saved_by: row.get(5)?,
+        });
+    }
+
+    Ok(items)
+}
+
+/// `/collections/<name>/feed.html`, see `handle_get_collection`
+pub async fn handle_get_collection_html(
+    State(state): State<App>,
+    Extension(user): Extension<Option<User>>,
+    Path(name): Path<String>,
+) -> Response<String> {
+    handle_get_collection(state, user, &name, |collection, items| {
+        let mut writer = HtmlWriter::new_public(
+            &collection.title,
+            &collection.description,
+            &format!("{}{}", collection.link, RssWriter::PATH),
+            SystemTime::now(),
+        );
+        writer.write_collection_items(items);
+        (HtmlWriter::CONTENT_TYPE, writer.finish())
+    })
+}
+
+/// `/collections/<name>/feed.xml`, see `handle_get_collection`
+pub async fn handle_get_collection_xml(
+    State(state): State<App>,
+    Extension(user): Extension<Option<User>>,
+    Path(name): Path<String>,
+) -> Response<String> {
+    handle_get_collection(state, user, &name, |collection, items| {
+        let mut writer = RssWriter::new_at(
+            &collection.title,
+            &collection.description,
+            &collection.link,
+            RssWriter::PATH,
+            SystemTime::now(),
+        );
+        writer.write_collection_items(items);
+        (RssWriter::CONTENT_TYPE, writer.finish())
+    })
+}
+
+/// Public collections can be read by anyone, the others only by their members
+fn handle_get_collection(
+    state: App,
+    user: Option<User>,
+    name: &str,
+    render: impl FnOnce(&Collection, Vec<CollectionItem>) -> (&'static str, String),
+) -> Response<String> {
+    info!(
+        "get_collection: {} {name}",
+        user.as_ref().map(|user| user.name.as_str()).unwrap_or("-")
+    );
+
+    let result = {
+        let mut db_lock = state.db_connection.lock().unwrap();
+
+        fetch_collection(&mut db_lock, name, &state.args).and_then(|collection| {
+            let Some(collection) = collection else {
+                return Ok(None);
+            };
+
+            let is_readable = collection.public
+                || match &user {
+                    Some(user) => is_member(&mut db_lock, &collection, user)?,
+                    None => false,
+                };
+            if !is_readable {
+                return Ok(Some((collection, None)));
+            }
+
+            expire_collection_articles(&mut db_lock, &state.args)?;
+            let items = fetch_collection_items(&mut db_lock, &collection, state.args.feed_limit)?;
+            Ok(Some((collection, Some(items))))
+        })
+    };
+
+    match result {
+        Ok(Some((collection, Some(items)))) => {
+            let (content_type, body) = render(&collection, items);
+            Response::builder()
+                .status(StatusCode::OK)
+                .header("Content-Type", content_type)
+                .body(body)
+                .unwrap()
+        }
+        // private collections ask for credentials like the feeds do
+        Ok(Some((_, None))) if user.is_none() => auth::unauthorized().map(|_| String::new()),
+        Ok(_) => Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .body(String::new())
+            .unwrap(),
+        Err(err) => {
+            error!("{err}");
+            Response::builder()
+                .status(StatusCode::INTERNAL_SERVER_ERROR)
+                .body(String::new())
+                .unwrap()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Args;
+    use crate::Article;
+    use crate::DocumentKind;
+    use crate::collection::CollectionCommand;
+    use crate::collection::fetch_collection;
+    use crate::collection::fetch_member_collection;
+    use crate::collection::fetch_members;
+    use crate::collection::remove_from_collection;
+    use crate::collection::run_collection_command;
+    use crate::collection::store_in_collection;
+    use crate::setup_tables;
+    use crate::user::DEFAULT_USER_ID;
+    use crate::user::fetch_user;
+    use argh::FromArgs;
+    use duckdb::Connection;
+    use url::Url;
+
+    fn run(db_connection: &mut Connection, args: &Args, command: &[&str]) -> anyhow::Result<()> {
+        let command = CollectionCommand::from_args(&["collection"], command).unwrap();
+        run_collection_command(db_connection, &command, args)
+    }
+
+    #[test]
+    fn members_share_a_collection() {
+        let mut db_connection = Connection::open_in_memory().unwrap();
+        setup_tables(&mut db_connection).unwrap();
+        let args = Args::from_args(&["paket"], &["-l", "https://paket.example/"]).unwrap();
+
+        run(
+            &mut db_connection,
+            &args,
+            &["create", "reads", "--title", "What we read", "--public"],
+        )
+        .unwrap();
+        assert!(run(&mut db_connection, &args, &["create", "reads"]).is_err());
+        assert!(run(&mut db_connection, &args, &["create", "a/b"]).is_err());
+        run(
+            &mut db_connection,
+            &args,
+            &["add-member", "reads", "default"],
+        )
+        .unwrap();
+        assert!(
+            run(
+                &mut db_connection,
+                &args,
+                &["add-member", "reads", "nobody"]
+            )
+            .is_err()
+        );
+
+        let collection = fetch_collection(&mut db_connection, "reads", &args)
+            .unwrap()
+            .unwrap();
+        assert_eq!(collection.title, "What we read");
+        assert_eq!(collection.description, "My links");
+        assert_eq!(collection.link, "https://paket.example/collections/reads/");
+        assert!(collection.public);
+        assert_eq!(
+            fetch_members(&mut db_connection, collection.id).unwrap(),
+            ["default"]
+        );
+
+        let user = fetch_user(&mut db_connection, DEFAULT_USER_ID, &args)
+            .unwrap()
+            .unwrap();
+        assert!(
+            fetch_member_collection(&mut db_connection, "reads", &user, &args)
+                .unwrap()
+                .is_some()
+        );
+
+        let article = || Article {
+            url: Url::parse("https://example.com/").unwrap(),
+            title: "Example".to_string(),
+            kind: DocumentKind::Html,
+        };
+        let guid = store_in_collection(&mut db_connection, &collection, &user, article()).unwrap();
+        assert_eq!(
+            guid,
+            store_in_collection(&mut db_connection, &collection, &user, article()).unwrap()
+        );
+        assert_ne!(guid, user.guid("https://example.com/"));
+
+        let count = |db_connection: &mut Connection| {
+            db_connection
+                .query_row("SELECT count(*) FROM collection_articles", [], |row| {
+                    row.get::<_, i64>(0)
+                })
+                .unwrap()
+        };
+        assert_eq!(count(&mut db_connection), 1);
+
+        run(
+            &mut db_connection,
+            &args,
+            &[
+                "update",
+                "reads",
+                "--link",
+                "https://reads.example/weekly",
+                "--public",
+                "false",
+            ],
+        )
+        .unwrap();
+        let collection = fetch_collection(&mut db_connection, "reads", &args)
+            .unwrap()
+            .unwrap();
+        assert_eq!(collection.link, "https://reads.example/weekly/");
+        assert!(!collection.public);
+
+        run(
+            &mut db_connection,
+            &args,
+            &["remove-member", "reads", "default"],
+        )
+        .unwrap();
+        assert!(
+            fetch_member_collection(&mut db_connection, "reads", &user, &args)
+                .unwrap()
+                .is_none()
+        );
+
+        assert!(remove_from_collection(&mut db_connection, &collection, &guid).unwrap());
+        assert!(!remove_from_collection(&mut db_connection, &collection, &guid).unwrap());
+
+        store_in_collection(&mut db_connection, &collection, &user, article()).unwrap();
+        run(&mut db_connection, &args, &["delete", "reads"]).unwrap();
+        assert_eq!(count(&mut db_connection), 0);
+    }
+}
@@ -0,0 +1,116 @@
+use crate::Args;
+use axum::http::HeaderName;
+use axum::http::HeaderValue;
+use axum::http::Method;
+use std::time::Duration;
+use tower_http::cors::AllowOrigin;
+use tower_http::cors::CorsLayer;
+
+/// How long browsers may reuse a preflight response
+const MAX_AGE: Duration = Duration::from_secs(60 * 60);
+
+/// Other origins allowed to call Paket from browsers, like the extension's popup
+#[derive(Debug, Clone)]
+pub enum CorsOrigins {
+    Any,
+    List(Vec<HeaderValue>),
+}
+
+pub fn parse_cors_origins(origins: &str) -> Result<CorsOrigins, String> {
+    if origins.trim() == "*" {
+        return Ok(CorsOrigins::Any);
+    }
+
+    let origins = origins
+        .split(',')
+        .map(str::trim)
+        .filter(|origin| !origin.is_empty())
+        .map(|origin| {
+            // browsers send the origin without a path, a trailing slash would never match
+            HeaderValue::from_str(origin.trim_end_matches('/'))
+                .map_err(|_| format!("invalid origin {origin}"))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if origins.is_empty() {
+        return Err(String::from("no origins"));
+    }
+
+    Ok(CorsOrigins::List(origins))
+}
+
+#[derive(Debug, Clone)]
+pub struct CorsMethods(Vec<Method>);
+
+#[derive(Debug, Clone)]
+pub struct CorsHeaders(Vec<HeaderName>);
+
+pub fn parse_cors_methods(methods: &str) -> Result<CorsMethods, String> {
+    methods
+        .split(',')
+        .map(str::trim)
+        .map(|method| {
+            Method::from_bytes(method.to_ascii_uppercase().as_bytes())
+                .map_err(|_| format!("invalid method {method}"))
+        })
+        .collect::<Result<_, _>>()
+        .map(CorsMethods)
+}
+
+pub fn parse_cors_headers(headers: &str) -> Result<CorsHeaders, String> {
+    headers
+        .split(',')
+        .map(str::trim)
+        .map(|header| {
+            HeaderName::from_bytes(header.as_bytes())
+                .map_err(|_| format!("invalid header {header}"))
+        })
+        .collect::<Result<_, _>>()
+        .map(CorsHeaders)
+}
+
+/// Answers preflight requests and lets the allowed origins read responses. Listed origins
+/// may send credentials, any origin may only send tokens in headers
+pub fn cors_layer(args: &Args) -> Option<CorsLayer> {
+    let origins = args.cors_origins.as_ref()?;
+
+    let layer = CorsLayer::new()
+        .allow_methods(args.cors_methods.0.clone())
+        .allow_headers(args.cors_headers.0.clone())
+        .max_age(MAX_AGE);
+
+    let layer = match origins {
+        CorsOrigins::Any => layer.allow_origin(AllowOrigin::any()),
+        CorsOrigins::List(origins) => layer
+            .allow_origin(AllowOrigin::list(origins.clone()))
+            .allow_credentials(true),
+    };
+
+    Some(layer)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cors::CorsOrigins;
+    use crate::cors::parse_cors_methods;
+    use crate::cors::parse_cors_origins;
+    use axum::http::Method;
+
+    #[test]
+    fn origins_and_methods() {
+        assert!(matches!(parse_cors_origins(" * "), Ok(CorsOrigins::Any)));
+        let Ok(CorsOrigins::List(origins)) =
+            parse_cors_origins("moz-extension://abc/, https://app.example")
+        else {
+            panic!("expected a list of origins");
+        };
+        assert_eq!(origins, ["moz-extension://abc", "https://app.example"]);
+        assert!(parse_cors_origins(",").is_err());
+
+        assert_eq!(
+            parse_cors_methods("get, put").unwrap().0,
+            [Method::GET, Method::PUT]
+        );
+        assert!(parse_cors_methods("get put").is_err());
+    }
+}
@@ -0,0 +1,356 @@
+use crate::App;
+use crate::auth::QueryToken;
+use crate::auth::hex;
+use crate::auth::unhex;
+use crate::user::User;
+use axum::body::Body;
+use axum::body::to_bytes;
+use axum::extract::Request;
+use axum::extract::State;
+use axum::http::HeaderMap;
+use axum::http::HeaderValue;
+use axum::http::StatusCode;
+use axum::http::header::COOKIE;
+use axum::http::header::HOST;
+use axum::http::header::ORIGIN;
+use axum::http::header::SET_COOKIE;
+use axum::middleware::Next;
+use axum::response::Response;
+use log::error;
+use log::warn;
+use ring::digest::SHA256;
+use ring::digest::digest;
+use ring::hmac;
+use ring::rand::SecureRandom;
+use ring::rand::SystemRandom;
+use url::Url;
+use url::form_urlencoded;
+
+const SESSION_COOKIE: &str = "paket_session";
+const SESSION_BYTES: usize = 32;
+/// Form field carrying the token
+pub const CSRF_FIELD: &str = "csrf";
+/// Largest form read to find the token, as axum's default body limit
+const MAX_FORM_SIZE: usize = 2 * 1024 * 1024;
+/// Tells the service worker whom a page was made for, it drops the pages kept for
+/// anyone else
+const VIEWER_HEADER: &str = "Paket-Viewer";
+
+/// Signs sessions into CSRF tokens. The key lives as long as the process,
+/// pages opened before a restart have to be reloaded
+pub struct Csrf {
+    key: hmac::Key,
+}
+
+/// Token of the requesting session, for the forms of a page
+#[derive(Debug, Clone)]
+pub struct CsrfToken(pub String);
+
+impl Csrf {
+    pub fn new() -> anyhow::Result<Self> {
+        let key = hmac::Key::generate(hmac::HMAC_SHA256, &SystemRandom::new())
+            .map_err(|_| anyhow::anyhow!("no randomness"))?;
+        Ok(Self { key })
+    }
+
+    fn token(&self, session: &str) -> String {
+        hex(hmac::sign(&self.key, session.as_bytes()).as_ref())
+    }
+
+    fn verify(&self, session: &str, token: &str) -> bool {
+        let Some(token) = unhex(token) else {
+            return false;
+        };
+        hmac::verify(&self.key, session.as_bytes(), &token).is_ok()
+    }
+}
+
+/// Hands the session's token to HTML pages, starting a session when there is none
+pub async fn issue_csrf_token(
+    State(state): State<App>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let session = session(request.headers()).map(ToString::to_string);
+
+    let (session, is_new) = match session {
+        Some(session) => (session, false),
+        None => match new_session() {
+            Some(session) => (session, true),
+            None => {
+                error!("no randomness");
+                return status(StatusCode::INTERNAL_SERVER_ERROR);
+            }
+        },
+    };
+
+    request
+        .extensions_mut()
+        .insert(CsrfToken(state.csrf.token(&session)));
+    let viewer = viewer(&session, request.extensions().get::<User>());
+
+    let mut response = next.run(request).await;
+
+    if let Ok(viewer) = HeaderValue::try_from(viewer) {
+        response.headers_mut().insert(VIEWER_HEADER, viewer);
+    }
+
+    if is_new {
+        let secure = state.args.link.starts_with("https:");
+        if let Ok(cookie) = HeaderValue::try_from(session_cookie(&session, secure)) {
+            response.headers_mut().append(SET_COOKIE, cookie);
+        }
+    }
+
+    response
+}
+
+/// Guards the routes posted to by the HTML forms. Cross-site requests are refused,
+/// browsers have to send the token of their session. Clients which are neither,
+/// like scripts, pass
+pub async fn require_csrf_token(
+    State(state): State<App>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let headers = request.headers();
+
+    if !is_same_site(headers, &state.args.link) {
+        warn!("cross-site request refused");
+        return status(StatusCode::FORBIDDEN);
+    }
+
+    let session = session(headers).map(ToString::to_string);
+    let is_browser =
+        session.is_some() || headers.contains_key(ORIGIN) || headers.contains_key("Sec-Fetch-Site");
+
+    if !is_browser {
+        return next.run(request).await;
+    }
+
+    let (parts, body) = request.into_parts();
+    let Ok(body) = to_bytes(body, MAX_FORM_SIZE).await else {
+        return status(StatusCode::PAYLOAD_TOO_LARGE);
+    };
+
+    let token = form_urlencoded::parse(&body)
+        .find(|(name, _)| name == CSRF_FIELD)
+        .map(|(_, token)| token.into_owned());
+
+    let is_valid = match (session, token) {
+        (Some(session), Some(token)) => state.csrf.verify(&session, &token),
+        _ => false,
+    };
+
+    if !is_valid {
+        warn!("missing or invalid csrf token");
+        return status(StatusCode::FORBIDDEN);
+    }
+
+    next.run(Request::from_parts(parts, Body::from(body))).await
+}
+
+/// Guards the links which save with a `GET`, `/add` and `/share`. Other sites can't
+/// have browsers follow them, unless they carry the bookmarklet's token
+pub async fn require_own_navigation(
+    State(state): State<App>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let is_bookmarklet = request.extensions().get::<QueryToken>().is_some();
+
+    if !is_bookmarklet && !is_own_navigation(request.headers(), &state.args.link) {
+        warn!("cross-site navigation refused");
+        return status(StatusCode::FORBIDDEN);
+    }
+
+    next.run(request).await
+}
+
+/// Requests from this site's pages, or navigations the user started, like a bookmark
+/// or the share sheet. Browsers without `Sec-Fetch-Site` are judged by `Origin`
+fn is_own_navigation(headers: &HeaderMap, link: &str) -> bool {
+    match headers.get("Sec-Fetch-Site") {
+        Some(site) => matches!(site.as_bytes(), b"same-origin" | b"none"),
+        None => is_same_site(headers, link),
+    }
+}
+
+/// `Origin` names this server, by the configured link or the `Host` it was reached at,
+/// and `Sec-Fetch-Site` doesn't tell of another site. Missing headers don't count against
+pub fn is_same_site(headers: &HeaderMap, link: &str) -> bool {
+    if let Some(site) = headers.get("Sec-Fetch-Site")
+        && !matches!(site.as_bytes(), b"same-origin" | b"none")
+    {
+        return false;
+    }
+
+    let Some(origin) = headers.get(ORIGIN) else {
+        return true;
+    };
+    let Some(origin) = origin
+        .to_str()
+        .ok()
+        .and_then(|origin| Url::parse(origin).ok())
+    else {
+        return false;
+    };
+
+    let is_link = Url::parse(link).is_ok_and(|link| link.origin() == origin.origin());
+
+    let is_host = headers
+        .get(HOST)
+        .and_then(|host| host.to_str().ok())
+        .zip(origin.host_str())
+        .is_some_and(|(host, origin_host)| {
+            let origin_host = match origin.port() {
+                Some(port) => format!("{origin_host}:{port}"),
+                None => origin_host.to_string(),
+            };
+            host.eq_ignore_ascii_case(&origin_host)
+        });
+
+    is_link || is_host
+}
+
+fn session(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get_all(COOKIE)
+        .iter()
+        .filter_map(|cookie| cookie.to_str().ok())
+        .flat_map(|cookie| cookie.split(';'))
+        .filter_map(|cookie| cookie.trim().split_once('='))
+        .find(|(name, _)| *name == SESSION_COOKIE)
+        .map(|(_, session)| session)
+        .filter(|session| !session.is_empty())
+}
+
+/// Digest of the session and the user, the session itself stays in its cookie
+fn viewer(session: &str, user: Option<&User>) -> String {
+    let user_id = user.map(|user| user.id.to_string()).unwrap_or_default();
+    hex(digest(&SHA256, format!("{session}:{user_id}").as_bytes()).as_ref())
+}
+
+fn new_session() -> Option<String> {
+    let mut bytes = [0; SESSION_BYTES];
+    SystemRandom::new().fill(&mut bytes).ok()?;
+    Some(hex(&bytes))
+}
+
+/// Strict same-site, so other sites' requests come without it
+fn session_cookie(session: &str, secure: bool) -> String {
+    let mut cookie = format!("{SESSION_COOKIE}={session}; Path=/; HttpOnly; SameSite=Strict");
+    if secure {
+        cookie.push_str("; Secure");
+    }
+    cookie
+}
+
+fn status(status: StatusCode) -> Response {
+    Response::builder()
+        .status(status)
+        .body(Body::empty())
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::csrf::Csrf;
+    use crate::csrf::is_own_navigation;
+    use crate::csrf::is_same_site;
+    use crate::csrf::session;
+    use crate::csrf::session_cookie;
+    use crate::csrf::viewer;
+    use crate::user::User;
+    use axum::http::HeaderMap;
+    use axum::http::HeaderValue;
+
+    #[test]
+    fn tokens_are_bound_to_session() {
+        let csrf = Csrf::new().unwrap();
+        let token = csrf.token("session");
+
+        assert!(csrf.verify("session", &token));
+        assert!(!csrf.verify("other", &token));
+        assert!(!csrf.verify("session", "not hex"));
+        assert!(!Csrf::new().unwrap().verify("session", &token));
+    }
+
+    #[test]
+    fn session_from_cookies() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(session(&headers), None);
+
+        headers.insert(
+            "Cookie",
+            HeaderValue::from_static("theme=dark; paket_session=abc"),
+        );
+        assert_eq!(session(&headers), Some("abc"));
+
+        assert_eq!(
+            session_cookie("abc", true),
+            "paket_session=abc; Path=/; HttpOnly; SameSite=Strict; Secure"
+        );
+    }
+
+    #[test]
+    fn viewers_differ_by_session_and_user() {
+        let user = |id| User {
+            id,
+            name: format!("user{id}"),
+            ttl: 60,
+            feed_name: String::new(),
+            feed_desc: String::new(),
+        };
+
+        let viewer_of_session = viewer("session", Some(&user(0)));
+        assert_eq!(viewer_of_session, viewer("session", Some(&user(0))));
+        assert_ne!(viewer_of_session, viewer("other", Some(&user(0))));
+        assert_ne!(viewer_of_session, viewer("session", Some(&user(1))));
+    }
+
+    #[test]
+    fn only_own_navigations() {
+        let link = "https://paket.example/";
+        let mut headers = HeaderMap::new();
+        assert!(is_own_navigation(&headers, link));
+
+        for (site, is_own) in [
+            ("same-origin", true),
+            ("none", true),
+            ("same-site", false),
+            ("cross-site", false),
+        ] {
+            headers.insert("Sec-Fetch-Site", HeaderValue::from_static(site));
+            assert_eq!(is_own_navigation(&headers, link), is_own, "{site}");
+        }
+
+        headers.remove("Sec-Fetch-Site");
+        headers.insert("Origin", HeaderValue::from_static("https://evil.example"));
+        assert!(!is_own_navigation(&headers, link));
+    }
+
+    #[test]
+    fn cross_site_requests() {
+        let link = "https://paket.example/";
+        let mut headers = HeaderMap::new();
+        assert!(is_same_site(&headers, link));
+
+        headers.insert("Origin", HeaderValue::from_static("https://paket.example"));
+        assert!(is_same_site(&headers, link));
+
+        headers.insert("Origin", HeaderValue::from_static("https://evil.example"));
+        assert!(!is_same_site(&headers, link));
+
+        headers.insert("Origin", HeaderValue::from_static("null"));
+        assert!(!is_same_site(&headers, link));
+
+        headers.insert("Origin", HeaderValue::from_static("http://localhost:8080"));
+        assert!(!is_same_site(&headers, link));
+        headers.insert("Host", HeaderValue::from_static("localhost:8080"));
+        assert!(is_same_site(&headers, link));
+
+        headers.insert("Sec-Fetch-Site", HeaderValue::from_static("cross-site"));
+        assert!(!is_same_site(&headers, link));
+    }
+}
@@ -0,0 +1,90 @@
+use crate::auth::hex;
+use crate::auth::unhex;
+use anyhow::anyhow;
+use duckdb::Connection;
+use duckdb::params;
+use ring::hmac;
+use ring::rand::SecureRandom;
+use ring::rand::SystemRandom;
+use url::Url;
+
+const KEY_NAME: &str = "extend";
+const KEY_BYTES: usize = 32;
+
+/// Signs the extend links put into feeds, so that following one can't be forged
+/// by another site. The key is kept in the database, links of feeds fetched
+/// before a restart keep working
+#[derive(Clone)]
+pub struct ExtendKey {
+    key: hmac::Key,
+}
+
+impl ExtendKey {
+    /// The stored key, created on first start
+    pub fn load(db_connection: &mut Connection) -> anyhow::Result<Self> {
+        let mut bytes = [0; KEY_BYTES];
+        SystemRandom::new()
+            .fill(&mut bytes)
+            .map_err(|_| anyhow!("no randomness"))?;
+
+        db_connection.execute(
+            "INSERT INTO keys (name, key) VALUES (?, ?) ON CONFLICT DO NOTHING",
+            params![KEY_NAME, hex(&bytes)],
+        )?;
+        let key =
+            db_connection.query_row("SELECT key FROM keys WHERE name = ?", [KEY_NAME], |row| {
+                row.get::<_, String>(0)
+            })?;
+        let key = unhex(&key).ok_or_else(|| anyhow!("invalid key {KEY_NAME}"))?;
+
+        Ok(Self {
+            key: hmac::Key::new(hmac::HMAC_SHA256, &key),
+        })
+    }
+
+    fn token(&self, guid: &str) -> String {
+        hex(hmac::sign(&self.key, guid.as_bytes()).as_ref())
+    }
+
+    pub fn verify(&self, guid: &str, token: &str) -> bool {
+        let Some(token) = unhex(token) else {
+            return false;
+        };
+        hmac::verify(&self.key, guid.as_bytes(), &token).is_ok()
+    }
+
+    /// `GET /extend` of the article, for readers which can only follow links
+    pub fn link(&self, link: &str, guid: &str) -> Option<Url> {
+        let mut url = Url::parse(link).ok()?.join("extend").ok()?;
+        url.query_pairs_mut()
+            .append_pair("guid", guid)
+            .append_pair("token", &self.token(guid));
+        Some(url)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::extend::ExtendKey;
+    use crate::setup_tables;
+    use duckdb::Connection;
+
+    #[test]
+    fn links_are_signed_with_stored_key() {
+        let mut db_connection = Connection::open_in_memory().unwrap();
+        setup_tables(&mut db_connection).unwrap();
+
+        let key = ExtendKey::load(&mut db_connection).unwrap();
+        let link = key.link("https://paket.example/", "guid").unwrap();
+        let token = key.token("guid");
+        assert_eq!(
+            link.as_str(),
+            format!("https://paket.example/extend?guid=guid&token={token}")
+        );
+
+        let reloaded = ExtendKey::load(&mut db_connection).unwrap();
+        assert!(reloaded.verify("guid", &token));
+        assert!(!reloaded.verify("other", &token));
+        assert!(!reloaded.verify("guid", "not hex"));
+    }
+}
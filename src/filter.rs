@@ -0,0 +1,173 @@
+use crate::DocumentKind;
+use serde::Deserialize;
+use serde::Deserializer;
+use serde::de::IntoDeserializer;
+use serde::de::value::StrDeserializer;
+use url::form_urlencoded;
+
+/// `?domain=`, `?tag=`, `?type=`, `?read=` and `?sort=` of a feed. Empty values match anything
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash, Deserialize)]
+pub struct FeedFilter {
+    #[serde(default, deserialize_with = "empty_as_none")]
+    pub domain: Option<String>,
+    #[serde(default, deserialize_with = "empty_as_none")]
+    pub tag: Option<String>,
+    #[serde(default, rename = "type", deserialize_with = "empty_as_none")]
+    pub kind: Option<DocumentKind>,
+    #[serde(default, deserialize_with = "empty_as_none")]
+    pub read: Option<ReadState>,
+    #[serde(default, deserialize_with = "empty_as_none")]
+    pub sort: Option<SortOrder>,
+    /// Only pinned articles. Set by the scope of a feed token, not by the query
+    #[serde(skip)]
+    pub pinned: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReadState {
+    Read,
+    Unread,
+}
+
+impl ReadState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReadState::Read => "read",
+            ReadState::Unread => "unread",
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    /// Newest first
+    #[default]
+    Saved,
+    /// Soonest to expire first
+    Expiry,
+    Title,
+    Domain,
+}
+
+impl SortOrder {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SortOrder::Saved => "saved",
+            SortOrder::Expiry => "expiry",
+            SortOrder::Title => "title",
+            SortOrder::Domain => "domain",
+        }
+    }
+
+    pub fn order_by(&self) -> &'static str {
+        match self {
+            SortOrder::Saved => "timestamp DESC, guid DESC",
+            SortOrder::Expiry => "coalesce(extended_at, timestamp) ASC",
+            SortOrder::Title => "lower(title) ASC, timestamp DESC",
+            SortOrder::Domain => {
+                "regexp_extract(link, '^[a-z]+://([^/:?#]+)', 1) ASC, timestamp DESC"
+            }
+        }
+    }
+}
+
+impl FeedFilter {
+    pub fn sort(&self) -> SortOrder {
+        self.sort.unwrap_or_default()
+    }
+
+    /// Domain without `www.`, lowercase. Subdomains match as well
+    pub fn domain(&self) -> Option<String> {
+        self.domain.as_deref().map(|domain| {
+            let domain = domain.trim().to_lowercase();
+            domain
+                .strip_prefix("www.")
+                .map(ToString::to_string)
+                .unwrap_or(domain)
+        })
+    }
+
+    pub fn tag(&self) -> Option<String> {
+        self.tag.as_deref().map(|tag| tag.trim().to_lowercase())
+    }
+
+    /// Query string parameters to carry over to other pages, starting with `&`
+    pub fn to_query(&self) -> String {
+        let mut serializer = form_urlencoded::Serializer::new(String::new());
+        if let Some(domain) = &self.domain {
+            serializer.append_pair("domain", domain);
+        }
+        if let Some(tag) = &self.tag {
+            serializer.append_pair("tag", tag);
+        }
+        if let Some(kind) = &self.kind {
+            serializer.append_pair("type", kind.as_str());
+        }
+        if let Some(read) = &self.read {
+            serializer.append_pair("read", read.as_str());
+        }
+        if let Some(sort) = &self.sort {
+            serializer.append_pair("sort", sort.as_str());
+        }
+
+        let query = serializer.finish();
+        if query.is_empty() {
+            query
+        } else {
+            format!("&{query}")
+        }
+    }
+}
+
+fn empty_as_none<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    let value = Option::<String>::deserialize(deserializer)?;
+    match value.as_deref().map(str::trim) {
+        None | Some("") => Ok(None),
+        Some(value) => {
+            let deserializer: StrDeserializer<D::Error> = value.into_deserializer();
+            T::deserialize(deserializer).map(Some)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::DocumentKind;
+    use crate::filter::FeedFilter;
+    use crate::filter::ReadState;
+    use crate::filter::SortOrder;
+    use axum::extract::Query;
+    use axum::http::Uri;
+
+    #[test]
+    fn filter_from_query() {
+        let uri: Uri = "/feed.html?domain=WWW.Example.com&tag=&type=pdf&read=unread&sort=title"
+            .parse()
+            .unwrap();
+        let Query(filter) = Query::<FeedFilter>::try_from_uri(&uri).unwrap();
+
+        assert_eq!(filter.domain().as_deref(), Some("example.com"));
+        assert_eq!(filter.tag(), None);
+        assert_eq!(filter.kind, Some(DocumentKind::Pdf));
+        assert_eq!(filter.read, Some(ReadState::Unread));
+        assert_eq!(filter.sort(), SortOrder::Title);
+        assert_eq!(
+            filter.to_query(),
+            "&domain=WWW.Example.com&type=pdf&read=unread&sort=title"
+        );
+
+        let uri: Uri = "/feed.xml?sort=random".parse().unwrap();
+        assert!(Query::<FeedFilter>::try_from_uri(&uri).is_err());
+
+        let uri: Uri = "/feed.xml".parse().unwrap();
+        let Query(filter) = Query::<FeedFilter>::try_from_uri(&uri).unwrap();
+        assert_eq!(filter, FeedFilter::default());
+        assert_eq!(filter.to_query(), "");
+    }
+}
@@ -1,138 +1,796 @@
 use crate::FeedItem;
 use crate::FeedWriter;
+use crate::TrashItem;
 use crate::WeeklyItem;
+use crate::collection::CollectionItem;
+use crate::csrf::CSRF_FIELD;
+use crate::filter::FeedFilter;
+use crate::markup::Markup;
+use crate::page::Pagination;
+use crate::push_notes;
+use crate::search::SearchQuery;
+use crate::search::Segment;
+use crate::stats::Stats;
 use httpdate::fmt_http_date;
+use std::slice;
 use std::time::SystemTime;
 
 const STYLE: &str = include_str!("../style.css");
 
 pub struct HtmlWriter {
-    buffer: String,
+    buffer: Markup,
+    csrf_token: Option<String>,
 }
 
-impl FeedWriter for HtmlWriter {
-    const CONTENT_TYPE: &str = "text/html";
+impl HtmlWriter {
+    pub fn write_trash_items(&mut self, items: Vec<TrashItem>) {
+        let buffer = &mut self.buffer;
+        let csrf_token = self.csrf_token.as_deref();
 
-    fn new(title: &str, description: &str, link: &str, date: SystemTime) -> Self {
-        let mut buffer = String::new();
+        buffer.push_raw("<ul class=\"feed-items\">");
 
-        let date = fmt_http_date(date);
+        for item in items {
+            buffer.push_raw("<li><article class=\"feed-item\"><h2><a href=\"");
+            buffer.push_url(&item.link);
+            buffer.push_raw("\">");
+            buffer.push_text(&item.title);
+            buffer.push_raw("</a></h2><div class=\"published-date\"> Deleted: ");
+            buffer.push_text(&item.deleted_date);
+            buffer.push_raw(" Purged: ");
+            buffer.push_text(&item.purge_date);
+            buffer.push_raw(
+                "</div><form method=\"POST\" action=\"/restore\" style=\"display: inline;\">",
+            );
+            write_csrf_input(buffer, csrf_token);
+            buffer.push_raw("<input type=\"hidden\" name=\"guid\" value=\"");
+            buffer.push_text(&item.guid);
+            buffer.push_raw("\"><button type=\"submit\" class=\"restore-btn\">Restore</button></form></article></li>");
+        }
 
-        buffer.push_str(
-            r#"
-            <!DOCTYPE html>
-            <html lang="en">
-            <head>
-                <meta charset="UTF-8">
-                <meta name="viewport" content="width=device-width, initial-scale=1.0">
-                <title>
-        "#,
-        );
+        buffer.push_raw("</ul>");
+    }
+}
 
-        buffer.push_str(title);
+impl HtmlWriter {
+    /// Read-only page without navigation, search or forms, for readers who aren't users
+    pub fn new_public(title: &str, description: &str, link: &str, date: SystemTime) -> Self {
+        let mut buffer = Markup::new();
 
-        buffer.push_str("</title><style>");
+        write_head(&mut buffer, title, description, link, date, false);
 
-        buffer.push_str(STYLE);
+        buffer.push_raw("</div>");
 
-        buffer.push_str("</style></head><body>");
+        Self {
+            buffer,
+            csrf_token: None,
+        }
+    }
 
-        buffer.push_str("<h1>");
-        buffer.push_str(title);
-        buffer.push_str("</h1>");
+    /// Articles link straight to their pages, there is nothing to mark as read
+    pub fn write_collection_items(&mut self, items: Vec<CollectionItem>) {
+        let buffer = &mut self.buffer;
 
-        buffer.push_str("<h3>");
-        buffer.push_str(description);
-        buffer.push_str("</h3>");
+        buffer.push_raw("<ul class=\"feed-items\">");
 
-        buffer.push_str("<div class=\"feed-info\">");
+        for item in items {
+            buffer.push_raw("<li><article class=\"feed-item\"><h2><a href=\"");
+            buffer.push_url(&item.link);
+            buffer.push_raw("\">");
+            buffer.push_text(&item.title);
+            buffer.push_raw("</a></h2><div class=\"published-date\"> Published: ");
+            buffer.push_text(&item.pub_date);
+            if !item.saved_by.is_empty() {
+                buffer.push_raw(" Saved by: ");
+                buffer.push_text(&item.saved_by);
+            }
+            buffer.push_raw("</div></article></li>");
+        }
 
-        buffer.push_str("<p>Feed: <a href=\"");
-        buffer.push_str(link);
-        buffer.push_str("\">");
-        buffer.push_str(link);
-        buffer.push_str("</a></p>");
+        buffer.push_raw("</ul>");
+    }
+}
+
+impl HtmlWriter {
+    /// The bookmarklet is only known right after its token was created
+    pub fn write_install(&mut self, token_name: &str, bookmarklet: Option<&str>) {
+        let buffer = &mut self.buffer;
+        let csrf_token = self.csrf_token.as_deref();
+
+        buffer.push_raw("<section class=\"install\"><h2>Bookmarklet</h2>");
+
+        match bookmarklet {
+            Some(bookmarklet) => {
+                buffer.push_raw("<p>Drag the link to the bookmarks bar. It saves the page you are on and brings you back. It holds the token ");
+                buffer.push_text(token_name);
+                buffer.push_raw(
+                    ", which can be revoked like any other.</p><p><a class=\"bookmarklet\" href=\"",
+                );
+                // generated here, push_url would refuse the javascript: link
+                buffer.push_text(bookmarklet);
+                buffer.push_raw("\">Save to Paket</a></p><textarea readonly rows=\"4\">");
+                buffer.push_text(bookmarklet);
+                buffer.push_raw("</textarea><p><a href=\"/install\">Back</a></p>");
+            }
+            None => {
+                buffer.push_raw("<p>A bookmarklet saves the page you are on from any browser. It gets a token of its own, creating it again replaces the token.</p><form method=\"POST\" action=\"/install\">");
+                write_csrf_input(buffer, csrf_token);
+                buffer.push_raw("<input type=\"text\" name=\"name\" value=\"");
+                buffer.push_text(token_name);
+                buffer.push_raw("\"><button type=\"submit\">Create bookmarklet</button></form>");
+            }
+        }
 
-        buffer.push_str("<p>Last Updated: ");
-        buffer.push_str(&date);
-        buffer.push_str("</p>");
+        buffer.push_raw("<h2>Share from a phone</h2><p>Install <a href=\"/feed.html\">the feed</a> as an app from the browser's menu. Paket then shows up when sharing links.</p></section>");
+    }
+}
 
-        buffer.push_str("</div>");
+impl HtmlWriter {
+    pub fn write_stats(&mut self, stats: &Stats) {
+        let buffer = &mut self.buffer;
 
-        buffer.push_str(
-            r#"
-            <div class="month-labels">
-                <span>Jan</span>
-                <span>Mar</span>
-                <span>Jun</span>
-                <span>Sep</span>
-                <span>Dec</span>
-            </div>
-            "#,
+        buffer.push_raw("<section class=\"stats\"><h2>Overview</h2><dl>");
+        write_stat(buffer, "Live articles", &stats.live_articles.to_string());
+        write_stat(
+            buffer,
+            "Unread articles",
+            &stats.unread_articles.to_string(),
+        );
+        write_stat(
+            buffer,
+            "Median time to read",
+            &stats
+                .median_hours_to_read
+                .map(|hours| format!("{hours:.1} hours"))
+                .unwrap_or_else(|| "-".to_string()),
         );
+        write_stat(
+            buffer,
+            "Expired unread",
+            &format_percent(stats.expired_unread_percent),
+        );
+        write_stat(
+            buffer,
+            "Largest backlog",
+            &stats
+                .largest_backlog
+                .as_ref()
+                .map(|backlog| format!("{} articles on {}", backlog.count, backlog.day))
+                .unwrap_or_else(|| "-".to_string()),
+        );
+        write_stat(
+            buffer,
+            "Fetch failures",
+            &format_percent(stats.fetch_failure_percent),
+        );
+        buffer.push_raw("</dl></section>");
+
+        buffer.push_raw("<section class=\"stats\"><h2>Top domains</h2><table>");
+        for domain in &stats.top_domains {
+            buffer.push_raw("<tr><td>");
+            buffer.push_text(&domain.domain);
+            buffer.push_raw("</td><td>");
+            buffer.push_text(&domain.count.to_string());
+            buffer.push_raw("</td></tr>");
+        }
+        buffer.push_raw("</table></section>");
+
+        let max_saves = stats
+            .saves_per_day
+            .iter()
+            .map(|day| day.count)
+            .max()
+            .unwrap_or(0);
+
+        buffer.push_raw("<section class=\"stats\"><h2>Saves per day</h2><div class=\"bars\" style=\"--max-articles: ");
+        buffer.push_text(&max_saves.to_string());
+        buffer.push_raw(";\">");
+        for day in &stats.saves_per_day {
+            buffer.push_raw("<div class=\"bar\" style=\"--articles: ");
+            buffer.push_text(&day.count.to_string());
+            buffer.push_raw(";\" title=\"");
+            buffer.push_text(&day.day);
+            buffer.push_raw(": ");
+            buffer.push_text(&day.count.to_string());
+            buffer.push_raw(" saved\"></div>");
+        }
+        buffer.push_raw("</div></section>");
+    }
+}
+
+impl FeedWriter for HtmlWriter {
+    const CONTENT_TYPE: &str = "text/html";
+    const PATH: &str = "feed.html";
+
+    fn new_at(title: &str, description: &str, link: &str, _: &str, date: SystemTime) -> Self {
+        let mut buffer = Markup::new();
+
+        write_head(&mut buffer, title, description, link, date, true);
+
+        buffer.push_raw("<p><a href=\"/feed.html\">Feed</a> <a href=\"/trash.html\">Trash</a> <a href=\"/stats\">Stats</a> <a href=\"/install\">Install</a></p>");
+
+        buffer.push_raw("</div>");
 
-        Self { buffer }
+        buffer.push_raw("<form class=\"search\" method=\"GET\" action=\"/search\"><input type=\"search\" name=\"q\" placeholder=\"Search\"><button type=\"submit\">Search</button></form>");
+
+        Self {
+            buffer,
+            csrf_token: None,
+        }
     }
 
-    fn write_weekly_items(&mut self, items: Vec<WeeklyItem>) {
-        assert!(items.len() <= 53);
+    fn set_csrf_token(&mut self, token: &str) {
+        self.csrf_token = Some(token.to_string());
+    }
 
+    fn write_undo_notice(&mut self, guid: &str) {
         let buffer = &mut self.buffer;
+        let csrf_token = self.csrf_token.as_deref();
+
+        buffer.push_raw("<div class=\"notice\">Article moved to trash. <form method=\"POST\" action=\"/restore\" style=\"display: inline;\">");
+        write_csrf_input(buffer, csrf_token);
+        buffer.push_raw("<input type=\"hidden\" name=\"guid\" value=\"");
+        buffer.push_text(guid);
+        buffer.push_raw(
+            "\"><button type=\"submit\" class=\"restore-btn\">Undo</button></form></div>",
+        );
+    }
 
-        let max_article_count = items
-            .iter()
-            .map(|item| item.articles_count)
-            .max()
-            .unwrap_or(0);
+    fn write_weekly_items(&mut self, items: Vec<WeeklyItem>) {
+        let buffer = &mut self.buffer;
+
+        buffer.push_raw("<div class=\"month-labels\">");
+
+        let mut previous_month = None;
+        for item in &items {
+            let month = month_label(&item.week);
+            buffer.push_raw("<span>");
+            if month != previous_month {
+                buffer.push_text(month.unwrap_or_default());
+            }
+            buffer.push_raw("</span>");
+            previous_month = month;
+        }
 
-        buffer.push_str("<div class=\"calendar\" style=\"--max-articles: ");
-        buffer.push_str(&max_article_count.to_string());
-        buffer.push_str(";\">");
+        buffer.push_raw("</div>");
 
-        let remaning_weeks = 53 - items.len();
+        let max_saved_count = items.iter().map(|item| item.saved).max().unwrap_or(0);
+
+        buffer.push_raw("<div class=\"calendar\" style=\"--max-articles: ");
+        buffer.push_text(&max_saved_count.to_string());
+        buffer.push_raw(";\">");
 
         for item in items {
-            buffer.push_str("<div class=\"week-square\" style=\"--articles: ");
-            buffer.push_str(&item.articles_count.to_string());
-            buffer.push_str(";\" title=\"");
-            buffer.push_str(&item.articles_count.to_string());
-            buffer.push_str(" articles\"></div>");
+            buffer.push_raw("<div class=\"week-square\" style=\"--articles: ");
+            buffer.push_text(&item.saved.to_string());
+            buffer.push_raw(";\" title=\"Week of ");
+            buffer.push_text(&item.week);
+            buffer.push_raw(": ");
+            buffer.push_text(&item.saved.to_string());
+            buffer.push_raw(" saved, ");
+            buffer.push_text(&item.read.to_string());
+            buffer.push_raw(" read, ");
+            buffer.push_text(&item.deleted.to_string());
+            buffer.push_raw(" deleted, ");
+            buffer.push_text(&item.expired.to_string());
+            buffer.push_raw(" expired\"></div>");
+        }
+
+        buffer.push_raw("</div>");
+    }
+
+    fn write_expiring_items(&mut self, items: Vec<FeedItem>, window: u32) {
+        if items.is_empty() {
+            return;
         }
 
-        for _ in 0..remaning_weeks {
-            buffer.push_str(
-                "<div class=\"week-square\" style=\"--articles: 0;\" title=\"0 articles\"></div>",
+        let buffer = &mut self.buffer;
+        let csrf_token = self.csrf_token.as_deref();
+
+        buffer.push_raw("<section class=\"expiring\"><h2>Expiring within ");
+        buffer.push_text(&window.to_string());
+        buffer.push_raw(if window == 1 { " day" } else { " days" });
+        buffer.push_raw("</h2><ul class=\"expiring-items\">");
+
+        for item in items {
+            buffer.push_raw("<li>");
+            write_article_link(buffer, &item.link, &item.guid);
+            buffer.push_text(&item.title);
+            buffer.push_raw("</a><span class=\"expire-date\"> Expires: ");
+            buffer.push_text(&item.expire_date);
+            buffer.push_raw("</span><form method=\"POST\" action=\"/extend?guid=");
+            buffer.push_text(&item.guid);
+            buffer.push_raw("\" style=\"display: inline;\">");
+            write_csrf_input(buffer, csrf_token);
+            buffer.push_raw(
+                "<button type=\"submit\" class=\"extend-btn\">Extend</button></form></li>",
             );
         }
 
-        buffer.push_str("</div>");
+        buffer.push_raw("</ul></section>");
     }
 
     fn write_feed_items(&mut self, items: Vec<FeedItem>) {
         let buffer = &mut self.buffer;
+        let csrf_token = self.csrf_token.as_deref();
 
-        buffer.push_str("<ul class=\"feed-items\">");
+        buffer.push_raw("<ul class=\"feed-items\">");
 
         for item in items {
-            buffer.push_str("<li><article class=\"feed-item\"><h2><a href=\"");
-            buffer.push_str(&item.link);
-            buffer.push_str("\">");
-            buffer.push_str(&item.title);
-            buffer.push_str("</a></h2><div class=\"published-date\"> Published: ");
-            buffer.push_str(&item.pub_date);
-            buffer.push_str("</div><form method=\"POST\" action=\"/delete\" style=\"display: inline;\"><input type=\"hidden\" name=\"guid\" value=\"");
-            buffer.push_str(&item.guid);
-            buffer.push_str("\"><button type=\"submit\" class=\"delete-btn\">Delete</button></form></article></li>");
+            buffer.push_raw("<li><article class=\"feed-item\"><h2>");
+            write_article_link(buffer, &item.link, &item.guid);
+            buffer.push_text(&item.title);
+            buffer.push_raw("</a></h2><div class=\"published-date\"> Published: ");
+            buffer.push_text(&item.pub_date);
+            buffer.push_raw(" Expires: ");
+            buffer.push_text(&item.expire_date);
+            buffer.push_raw("</div>");
+
+            if !item.tags.is_empty() {
+                buffer.push_raw("<ul class=\"tags\">");
+                for tag in &item.tags {
+                    buffer.push_raw("<li>");
+                    buffer.push_text(tag);
+                    buffer.push_raw("</li>");
+                }
+                buffer.push_raw("</ul>");
+            }
+
+            for note in &item.notes {
+                buffer.push_raw("<div class=\"note\">");
+                push_notes(buffer, slice::from_ref(note));
+                buffer.push_raw("<details class=\"edit-note\"><summary>Edit</summary><form method=\"POST\" action=\"/note/edit\">");
+                write_csrf_input(buffer, csrf_token);
+                buffer.push_raw("<input type=\"hidden\" name=\"id\" value=\"");
+                buffer.push_text(&note.id.to_string());
+                buffer.push_raw("\"><textarea name=\"text\" rows=\"3\">");
+                buffer.push_text(&note.text);
+                buffer
+                    .push_raw("</textarea><button type=\"submit\">Save</button></form></details>");
+                buffer.push_raw(
+                    "<form method=\"POST\" action=\"/note/delete\" style=\"display: inline;\">",
+                );
+                write_csrf_input(buffer, csrf_token);
+                buffer.push_raw("<input type=\"hidden\" name=\"id\" value=\"");
+                buffer.push_text(&note.id.to_string());
+                buffer.push_raw(
+                    "\"><button type=\"submit\" class=\"delete-btn\">Remove</button></form></div>",
+                );
+            }
+
+            buffer.push_raw("<details class=\"edit-article\"><summary>Edit</summary><form method=\"POST\" action=\"/edit\">");
+            write_csrf_input(buffer, csrf_token);
+            buffer.push_raw("<input type=\"hidden\" name=\"guid\" value=\"");
+            buffer.push_text(&item.guid);
+            buffer.push_raw("\"><label>Title <input type=\"text\" name=\"title\" value=\"");
+            buffer.push_text(&item.title);
+            buffer.push_raw("\"></label><label>Link <input type=\"url\" name=\"link\" value=\"");
+            buffer.push_text(&item.link);
+            buffer.push_raw("\"></label><label>Tags <input type=\"text\" name=\"tags\" value=\"");
+            buffer.push_text(&item.tags.join(", "));
+            buffer.push_raw("\"></label><button type=\"submit\">Save</button></form><form method=\"POST\" action=\"/refetch\">");
+            write_csrf_input(buffer, csrf_token);
+            buffer.push_raw("<input type=\"hidden\" name=\"guid\" value=\"");
+            buffer.push_text(&item.guid);
+            buffer.push_raw("\"><button type=\"submit\">Re-fetch title</button></form></details>");
+
+            buffer.push_raw("<details class=\"add-note\"><summary>Add note</summary><form method=\"POST\" action=\"/note\">");
+            write_csrf_input(buffer, csrf_token);
+            buffer.push_raw("<input type=\"hidden\" name=\"guid\" value=\"");
+            buffer.push_text(&item.guid);
+            buffer.push_raw("\"><textarea name=\"text\" rows=\"3\" required></textarea><select name=\"kind\"><option value=\"note\">Note</option><option value=\"highlight\">Highlight</option></select><button type=\"submit\">Add</button></form></details>");
+
+            buffer.push_raw("<form method=\"POST\" action=\"/delete\" style=\"display: inline;\">");
+            write_csrf_input(buffer, csrf_token);
+            buffer.push_raw("<input type=\"hidden\" name=\"guid\" value=\"");
+            buffer.push_text(&item.guid);
+            buffer.push_raw("\"><button type=\"submit\" class=\"delete-btn\">Delete</button></form></article></li>");
         }
 
-        buffer.push_str("</ul>");
+        buffer.push_raw("</ul>");
+    }
+
+    fn write_search_items(&mut self, query: &SearchQuery, items: Vec<FeedItem>) {
+        let buffer = &mut self.buffer;
+
+        buffer.push_raw("<p class=\"search-summary\">");
+        buffer.push_text(&items.len().to_string());
+        buffer.push_raw(" results for \"");
+        buffer.push_text(query.as_str());
+        buffer.push_raw("\"</p>");
+
+        buffer.push_raw("<ul class=\"feed-items\">");
+
+        for item in items {
+            buffer.push_raw("<li><article class=\"feed-item\"><h2>");
+            write_article_link(buffer, &item.link, &item.guid);
+            write_highlighted(buffer, query, &item.title);
+            buffer.push_raw("</a></h2><div class=\"link\">");
+            write_highlighted(buffer, query, &item.link);
+            buffer.push_raw("</div><div class=\"published-date\"> Published: ");
+            buffer.push_text(&item.pub_date);
+            buffer.push_raw(" Expires: ");
+            buffer.push_text(&item.expire_date);
+            buffer.push_raw("</div></article></li>");
+        }
+
+        buffer.push_raw("</ul>");
+    }
+
+    fn write_filter(&mut self, filter: &FeedFilter) {
+        let buffer = &mut self.buffer;
+
+        buffer.push_raw("<form class=\"filter\" method=\"GET\" action=\"/feed.html\">");
+        buffer.push_raw("<input type=\"text\" name=\"domain\" placeholder=\"Domain\" value=\"");
+        buffer.push_text(filter.domain.as_deref().unwrap_or_default());
+        buffer.push_raw("\"><input type=\"text\" name=\"tag\" placeholder=\"Tag\" value=\"");
+        buffer.push_text(filter.tag.as_deref().unwrap_or_default());
+        buffer.push_raw("\">");
+        write_select(
+            buffer,
+            "type",
+            &[
+                ("", "Any type"),
+                ("html", "HTML"),
+                ("pdf", "PDF"),
+                ("other", "Other"),
+            ],
+            filter.kind.map(|kind| kind.as_str()).unwrap_or_default(),
+        );
+        write_select(
+            buffer,
+            "read",
+            &[
+                ("", "Read or unread"),
+                ("unread", "Unread"),
+                ("read", "Read"),
+            ],
+            filter.read.map(|read| read.as_str()).unwrap_or_default(),
+        );
+        write_select(
+            buffer,
+            "sort",
+            &[
+                ("saved", "Newest"),
+                ("expiry", "Expiring first"),
+                ("title", "Title"),
+                ("domain", "Domain"),
+            ],
+            filter.sort().as_str(),
+        );
+        buffer.push_raw("<button type=\"submit\">Filter</button></form>");
+    }
+
+    fn write_pagination(&mut self, pagination: &Pagination) {
+        if pagination.previous.is_none() && pagination.next.is_none() {
+            return;
+        }
+
+        let buffer = &mut self.buffer;
+
+        buffer.push_raw("<nav class=\"pagination\">");
+        if let Some(previous) = &pagination.previous {
+            buffer.push_raw("<a href=\"");
+            buffer.push_url(&pagination.first);
+            buffer.push_raw("\">First</a><a href=\"");
+            buffer.push_url(previous);
+            buffer.push_raw("\" rel=\"prev\">Previous</a>");
+        }
+        if let Some(next) = &pagination.next {
+            buffer.push_raw("<a href=\"");
+            buffer.push_url(next);
+            buffer.push_raw("\" rel=\"next\">Next</a>");
+        }
+        if let Some(last) = pagination
+            .last
+            .as_ref()
+            .filter(|_| pagination.next.is_some())
+        {
+            buffer.push_raw("<a href=\"");
+            buffer.push_url(last);
+            buffer.push_raw("\">Last</a>");
+        }
+        buffer.push_raw("</nav>");
     }
 
     fn finish(self) -> String {
         let mut buffer = self.buffer;
 
-        buffer.push_str("</body></html>");
+        buffer.push_raw("</body></html>");
+
+        buffer.into_string()
+    }
+}
+
+/// Document head, title, description and feed info, left open for the navigation.
+/// Pages of the app link the manifest, so that browsers offer to install it, and
+/// register the service worker
+fn write_head(
+    buffer: &mut Markup,
+    title: &str,
+    description: &str,
+    link: &str,
+    date: SystemTime,
+    is_app: bool,
+) {
+    let date = fmt_http_date(date);
+
+    buffer.push_raw(
+        r#"
+            <!DOCTYPE html>
+            <html lang="en">
+            <head>
+                <meta charset="UTF-8">
+                <meta name="viewport" content="width=device-width, initial-scale=1.0">
+                <title>
+        "#,
+    );
+
+    buffer.push_text(title);
+
+    buffer.push_raw("</title><style>");
+
+    buffer.push_raw(STYLE);
+
+    buffer.push_raw("</style>");
+
+    buffer.push_raw("<link rel=\"icon\" type=\"image/svg+xml\" href=\"/icon.svg\">");
+
+    if is_app {
+        buffer.push_raw("<link rel=\"manifest\" href=\"/manifest.webmanifest\">");
+        buffer.push_raw("<script src=\"/app.js\" defer></script>");
+    }
+
+    buffer.push_raw("</head><body>");
+
+    buffer.push_raw("<h1>");
+    buffer.push_text(title);
+    buffer.push_raw("</h1>");
+
+    buffer.push_raw("<h3>");
+    buffer.push_text(description);
+    buffer.push_raw("</h3>");
+
+    buffer.push_raw("<div class=\"feed-info\">");
+
+    buffer.push_raw("<p>Feed: <a href=\"");
+    buffer.push_url(link);
+    buffer.push_raw("\">");
+    buffer.push_text(link);
+    buffer.push_raw("</a></p>");
+
+    buffer.push_raw("<p>Last Updated: ");
+    buffer.push_text(&date);
+    buffer.push_raw("</p>");
+}
+
+/// Hidden field checked by the handlers of the form, see `csrf::require_csrf_token`
+fn write_csrf_input(buffer: &mut Markup, token: Option<&str>) {
+    let Some(token) = token else {
+        return;
+    };
+
+    buffer.push_raw("<input type=\"hidden\" name=\"");
+    buffer.push_raw(CSRF_FIELD);
+    buffer.push_raw("\" value=\"");
+    buffer.push_text(token);
+    buffer.push_raw("\">");
+}
+
+/// Opens the article itself; the browser pings `POST /read` only when the link
+/// is followed, so prefetching doesn't count as a read
+fn write_article_link(buffer: &mut Markup, link: &str, guid: &str) {
+    buffer.push_raw("<a href=\"");
+    buffer.push_url(link);
+    buffer.push_raw("\" ping=\"/read?guid=");
+    buffer.push_text(guid);
+    buffer.push_raw("\">");
+}
+
+fn write_highlighted(buffer: &mut Markup, query: &SearchQuery, text: &str) {
+    for segment in query.highlight(text) {
+        match segment {
+            Segment::Plain(text) => buffer.push_text(text),
+            Segment::Match(text) => {
+                buffer.push_raw("<mark>");
+                buffer.push_text(text);
+                buffer.push_raw("</mark>");
+            }
+        }
+    }
+}
+
+/// Short month name of a `%Y-%m-%d` date
+fn month_label(date: &str) -> Option<&'static str> {
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+
+    let month = date.split('-').nth(1)?.parse::<usize>().ok()?;
+    MONTHS.get(month.checked_sub(1)?).copied()
+}
+
+fn write_select(
+    buffer: &mut Markup,
+    name: &'static str,
+    options: &[(&'static str, &'static str)],
+    selected: &str,
+) {
+    buffer.push_raw("<select name=\"");
+    buffer.push_raw(name);
+    buffer.push_raw("\">");
+    for (value, label) in options {
+        buffer.push_raw("<option value=\"");
+        buffer.push_raw(value);
+        if *value == selected {
+            buffer.push_raw("\" selected>");
+        } else {
+            buffer.push_raw("\">");
+        }
+        buffer.push_raw(label);
+        buffer.push_raw("</option>");
+    }
+    buffer.push_raw("</select>");
+}
+
+fn write_stat(buffer: &mut Markup, name: &str, value: &str) {
+    buffer.push_raw("<dt>");
+    buffer.push_text(name);
+    buffer.push_raw("</dt><dd>");
+    buffer.push_text(value);
+    buffer.push_raw("</dd>");
+}
+
+fn format_percent(percent: Option<f64>) -> String {
+    percent
+        .map(|percent| format!("{percent:.1}%"))
+        .unwrap_or_else(|| "-".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::DocumentKind;
+    use crate::FeedItem;
+    use crate::FeedWriter;
+    use crate::TrashItem;
+    use crate::html::HtmlWriter;
+    use std::time::SystemTime;
+
+    #[test]
+    fn hostile_input_is_escaped() {
+        let mut writer = HtmlWriter::new(
+            "</title><script>alert(1)</script>",
+            "Links & more",
+            "javascript:alert(1)",
+            SystemTime::UNIX_EPOCH,
+        );
+        writer.write_undo_notice("\"><script>alert(1)</script>");
+        writer.write_trash_items(vec![TrashItem {
+            title: "<img src=x onerror=alert(1)>".to_string(),
+            link: "javascript:alert(1)".to_string(),
+            deleted_date: String::new(),
+            purge_date: String::new(),
+            guid: "guid".to_string(),
+        }]);
+        let page = writer.finish();
+
+        assert!(!page.contains("<script>"));
+        assert!(!page.contains("<img"));
+        assert!(!page.contains("href=\"javascript:"));
+        assert!(page.contains("&lt;/title&gt;&lt;script&gt;alert(1)&lt;/script&gt;"));
+        assert!(page.contains("value=\"&quot;&gt;&lt;script&gt;"));
+        assert!(page.contains("<a href=\"#\">&lt;img src=x onerror=alert(1)&gt;</a>"));
+    }
+
+    #[test]
+    fn forms_carry_csrf_token() {
+        let mut writer = HtmlWriter::new(
+            "Paket",
+            "Links",
+            "https://paket.example/",
+            SystemTime::UNIX_EPOCH,
+        );
+        writer.set_csrf_token("token");
+        writer.write_undo_notice("guid");
+        writer.write_trash_items(vec![TrashItem {
+            title: "Title".to_string(),
+            link: "https://example.com/".to_string(),
+            deleted_date: String::new(),
+            purge_date: String::new(),
+            guid: "guid".to_string(),
+        }]);
+        let page = writer.finish();
+
+        let forms = page.matches("<form method=\"POST\"").count();
+        assert_eq!(forms, 2);
+        assert_eq!(
+            page.matches("<input type=\"hidden\" name=\"csrf\" value=\"token\">")
+                .count(),
+            forms
+        );
+    }
+
+    #[test]
+    fn expiring_section_names_window() {
+        let mut writer = HtmlWriter::new(
+            "Paket",
+            "Links",
+            "https://paket.example/",
+            SystemTime::UNIX_EPOCH,
+        );
+        writer.write_expiring_items(Vec::new(), 3);
+        writer.write_expiring_items(
+            vec![FeedItem {
+                title: "Example".to_string(),
+                link: "https://example.com/".to_string(),
+                pub_date: String::new(),
+                expire_date: "Fri, 02 Jan 1970 00:00:00 GMT".to_string(),
+                pub_time: SystemTime::UNIX_EPOCH,
+                guid: "guid".to_string(),
+                kind: DocumentKind::Html,
+                tags: Vec::new(),
+                notes: Vec::new(),
+            }],
+            3,
+        );
+        let page = writer.finish();
+
+        assert_eq!(page.matches("<section class=\"expiring\">").count(), 1);
+        assert!(page.contains("<h2>Expiring within 3 days</h2>"));
+        assert!(page.contains("Expires: Fri, 02 Jan 1970 00:00:00 GMT"));
+        assert!(page.contains("action=\"/extend?guid=guid\""));
+    }
+
+    #[test]
+    fn article_links_ping_read() {
+        let item = |link: &str, guid: &str| FeedItem {
+            title: "Example".to_string(),
+            link: link.to_string(),
+            pub_date: String::new(),
+            expire_date: String::new(),
+            pub_time: SystemTime::UNIX_EPOCH,
+            guid: guid.to_string(),
+            kind: DocumentKind::Html,
+            tags: Vec::new(),
+            notes: Vec::new(),
+        };
+        let mut writer = HtmlWriter::new(
+            "Paket",
+            "Links",
+            "https://paket.example/",
+            SystemTime::UNIX_EPOCH,
+        );
+        writer.write_feed_items(vec![
+            item("https://example.com/", "first"),
+            item("javascript:alert(1)", "second"),
+        ]);
+        let page = writer.finish();
+
+        assert!(page.contains("<a href=\"https://example.com/\" ping=\"/read?guid=first\">"));
+        assert!(page.contains("<a href=\"#\" ping=\"/read?guid=second\">"));
+        assert!(!page.contains("href=\"/read"));
+    }
 
-        buffer
+    #[test]
+    fn only_app_pages_are_installable() {
+        let page = HtmlWriter::new(
+            "Paket",
+            "Links",
+            "https://paket.example/",
+            SystemTime::UNIX_EPOCH,
+        )
+        .finish();
+        assert!(page.contains("<link rel=\"manifest\" href=\"/manifest.webmanifest\">"));
+        assert!(page.contains("<script src=\"/app.js\" defer></script>"));
+
+        let page = HtmlWriter::new_public(
+            "What we read",
+            "Links",
+            "https://paket.example/collections/reads/feed.xml",
+            SystemTime::UNIX_EPOCH,
+        )
+        .finish();
+        assert!(page.contains("<link rel=\"icon\""));
+        assert!(!page.contains("manifest"));
+        assert!(!page.contains("<script"));
     }
 }
@@ -0,0 +1,259 @@
+use crate::App;
+use crate::FeedWriter;
+use crate::SaveForm;
+use crate::auth;
+use crate::csrf::CsrfToken;
+use crate::html::HtmlWriter;
+use crate::parse_http_url;
+use crate::save_article;
+use crate::user::User;
+use axum::Extension;
+use axum::Form;
+use axum::extract::Query;
+use axum::extract::State;
+use axum::http::response::Response;
+use axum::http::status::StatusCode;
+use axum::response::Redirect;
+use log::error;
+use log::info;
+use serde::Deserialize;
+use serde_json::json;
+use std::time::SystemTime;
+
+const MANIFEST_CONTENT_TYPE: &str = "application/manifest+json";
+
+const APP: &str = include_str!("../app.js");
+const SERVICE_WORKER: &str = include_str!("../sw.js");
+const ICON: &str = include_str!("../paket-firefox/icon.svg");
+
+/// Browsers look for a new service worker on every visit
+const SERVICE_WORKER_CACHE_CONTROL: &str = "no-cache";
+
+#[derive(Debug, Deserialize)]
+pub struct InstallForm {
+    /// name of the bookmarklet's token, an existing token of the user is replaced
+    name: String,
+}
+
+/// What the system share sheet hands over, see `share_target` in the manifest
+#[derive(Debug, Deserialize)]
+pub struct ShareQuery {
+    title: Option<String>,
+    text: Option<String>,
+    url: Option<String>,
+}
+
+impl ShareQuery {
+    /// Apps often leave out the url and put it at the end of the text instead
+    fn link(&self) -> Option<String> {
+        let url = self.url.iter().map(String::as_str);
+        let words = self.text.iter().flat_map(|text| text.split_whitespace());
+
+        url.chain(words)
+            .find_map(|candidate| parse_http_url(candidate.trim()).ok())
+    }
+}
+
+/// Lets browsers install `/feed.html` as an app which links can be shared to
+pub async fn handle_get_manifest(State(state): State<App>) -> Response<String> {
+    let manifest = json!({
+        "name": state.args.name,
+        "short_name": state.args.name,
+        "description": state.args.desc,
+        "start_url": "/feed.html",
+        "scope": "/",
+        "display": "standalone",
+        "theme_color": "#E74C3C",
+        "background_color": "#FFFFFF",
+        "icons": [{
+            "src": "/icon.svg",
+            "sizes": "any",
+            "type": "image/svg+xml",
+        }],
+        "share_target": {
+            "action": "/share",
+            "method": "GET",
+            "params": {
+                "title": "title",
+                "text": "text",
+                "url": "url",
+            },
+        },
+    });
+
+    Response::builder()
+        .status(StatusCode::OK)
+        .header("Content-Type", MANIFEST_CONTENT_TYPE)
+        .body(manifest.to_string())
+        .unwrap()
+}
+
+/// Registers the service worker, which keeps the pages readable offline
+pub async fn handle_get_app() -> Response<String> {
+    static_file("text/javascript", APP)
+}
+
+pub async fn handle_get_service_worker() -> Response<String> {
+    let mut response = static_file("text/javascript", SERVICE_WORKER);
+    if let Ok(cache_control) = SERVICE_WORKER_CACHE_CONTROL.parse() {
+        response
+            .headers_mut()
+            .insert("Cache-Control", cache_control);
+    }
+    response
+}
+
+pub async fn handle_get_icon() -> Response<String> {
+    static_file("image/svg+xml", ICON)
+}
+
+fn static_file(content_type: &str, body: &str) -> Response<String> {
+    Response::builder()
+        .status(StatusCode::OK)
+        .header("Content-Type", content_type)
+        .body(body.to_string())
+        .unwrap()
+}
+
+/// Saves what was shared to the installed app and opens the feed. The share sheet
+/// opens it as a navigation of the user, see `csrf::require_own_navigation`
+pub async fn handle_share(
+    State(state): State<App>,
+    Extension(user): Extension<User>,
+    Query(share): Query<ShareQuery>,
+) -> Result<Redirect, StatusCode> {
+    info!("share: {} {share:?}", user.name);
+
+    let Some(url) = share.link() else {
+        return Err(StatusCode::BAD_REQUEST);
+    };
+    let save = SaveForm {
+        url,
+        collection: None,
+        title: share.title,
+    };
+
+    match save_article(state, &user, &save).await {
+        StatusCode::OK => Ok(Redirect::to("/feed.html")),
+        status => Err(status),
+    }
+}
+
+/// Explains the ways to save from a browser and offers to create a bookmarklet
+pub async fn handle_get_install(
+    State(state): State<App>,
+    Extension(user): Extension<User>,
+    Extension(csrf_token): Extension<CsrfToken>,
+) -> Response<String> {
+    info!("get_install: {}", user.name);
+
+    let mut writer = install_page(&state, &user);
+    writer.set_csrf_token(&csrf_token.0);
+    writer.write_install(&format!("{}-bookmarklet", user.name), None);
+
+    Response::builder()
+        .status(StatusCode::OK)
+        .header("Content-Type", HtmlWriter::CONTENT_TYPE)
+        .body(writer.finish())
+        .unwrap()
+}
+
+/// Creates a token for the bookmarklet, it is only shown this once
+pub async fn handle_create_bookmarklet(
+    State(state): State<App>,
+    Extension(user): Extension<User>,
+    Form(install): Form<InstallForm>,
+) -> Response<String> {
+    info!("create_bookmarklet: {} {install:?}", user.name);
+
+    let name = install.name.trim();
+    if name.is_empty() {
+        return Response::builder()
+            .status(StatusCode::BAD_REQUEST)
+            .body(String::new())
+            .unwrap();
+    }
+
+    let token = {
+        let mut db_lock = state.db_connection.lock().unwrap();
+        auth::create_token(&mut db_lock, name, user.id)
+    };
+
+    let token = match token {
+        Ok(token) => token,
+        Err(err) => {
+            error!("{err}");
+            return Response::builder()
+                .status(StatusCode::INTERNAL_SERVER_ERROR)
+                .body(String::new())
+                .unwrap();
+        }
+    };
+
+    let mut writer = install_page(&state, &user);
+    writer.write_install(name, Some(&bookmarklet(&state.args.link, &token)));
+
+    Response::builder()
+        .status(StatusCode::OK)
+        .header("Content-Type", HtmlWriter::CONTENT_TYPE)
+        .body(writer.finish())
+        .unwrap()
+}
+
+fn install_page(state: &App, user: &User) -> HtmlWriter {
+    let title = format!("{}: install", user.feed_name);
+    HtmlWriter::new(
+        &title,
+        &user.feed_desc,
+        state.args.link.as_str(),
+        SystemTime::now(),
+    )
+}
+
+/// Opens `/add` with the page the browser is on and comes back to it. Browsers
+/// decode `javascript:` links before running them, so `%` is encoded once more
+fn bookmarklet(link: &str, token: &str) -> String {
+    let add = json!(format!("{link}add?redirect=back&access_token={token}"));
+    let code = format!(
+        "javascript:location.href={add}+'&url='+encodeURIComponent(location.href)+'&title='+encodeURIComponent(document.title)"
+    );
+    code.replace('%', "%25")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::install::ShareQuery;
+    use crate::install::bookmarklet;
+
+    #[test]
+    fn bookmarklet_opens_add() {
+        let bookmarklet = bookmarklet("https://paket.example/", "paket_abc");
+
+        assert!(bookmarklet.starts_with(
+            "javascript:location.href=\"https://paket.example/add?redirect=back&access_token=paket_abc\"+"
+        ));
+        assert!(bookmarklet.contains("encodeURIComponent(document.title)"));
+    }
+
+    #[test]
+    fn shared_link_from_text() {
+        let share = |url: Option<&str>, text: Option<&str>| ShareQuery {
+            title: None,
+            text: text.map(ToString::to_string),
+            url: url.map(ToString::to_string),
+        };
+
+        assert_eq!(
+            share(Some("https://example.com/a"), Some("https://example.com/b")).link(),
+            Some("https://example.com/a".to_string())
+        );
+        assert_eq!(
+            share(None, Some("Read this https://example.com/b")).link(),
+            Some("https://example.com/b".to_string())
+        );
+        assert_eq!(
+            share(Some("ftp://example.com/"), Some("nothing")).link(),
+            None
+        );
+    }
+}
@@ -0,0 +1,199 @@
+use crate::FeedItem;
+use crate::FeedWriter;
+use crate::atom::fmt_rfc3339;
+use crate::markup::Markup;
+use crate::page::Pagination;
+use crate::page::page_link;
+use crate::push_notes;
+use crate::rss::enclosure_type;
+use crate::search::SearchQuery;
+use serde::Serialize;
+use std::time::SystemTime;
+use url::Url;
+
+/// JSON Feed 1.1, https://www.jsonfeed.org/version/1.1/
+pub struct JsonFeedWriter {
+    feed: JsonFeed,
+    path: String,
+}
+
+#[derive(Serialize)]
+struct JsonFeed {
+    version: &'static str,
+    title: String,
+    description: String,
+    home_page_url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    feed_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    next_url: Option<String>,
+    items: Vec<JsonFeedItem>,
+}
+
+#[derive(Serialize)]
+struct JsonFeedItem {
+    id: String,
+    url: String,
+    title: String,
+    date_published: String,
+    summary: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    content_html: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    content_text: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<String>,
+    /// The link itself when it points at an image
+    #[serde(skip_serializing_if = "Option::is_none")]
+    image: Option<String>,
+}
+
+impl FeedWriter for JsonFeedWriter {
+    const CONTENT_TYPE: &str = "application/feed+json";
+    const PATH: &str = "feed.json";
+
+    fn new_at(title: &str, description: &str, link: &str, path: &str, _: SystemTime) -> Self {
+        let feed_url = Url::parse(link)
+            .ok()
+            .and_then(|url| url.join(path).ok())
+            .map(String::from);
+
+        let feed = JsonFeed {
+            version: "https://jsonfeed.org/version/1.1",
+            title: title.to_string(),
+            description: description.to_string(),
+            home_page_url: link.to_string(),
+            feed_url,
+            next_url: None,
+            items: Vec::new(),
+        };
+
+        Self {
+            feed,
+            path: path.to_string(),
+        }
+    }
+
+    fn write_feed_items(&mut self, items: Vec<FeedItem>) {
+        for item in items {
+            let summary = format!("Expires: {}", item.expire_date);
+
+            // items need content, without notes it is the summary
+            let (content_html, content_text) = if item.notes.is_empty() {
+                (None, Some(summary.clone()))
+            } else {
+                let mut content = Markup::new();
+                push_notes(&mut content, &item.notes);
+                (Some(content.into_string()), None)
+            };
+            let image = enclosure_type(&item.link)
+                .filter(|media_type| media_type.starts_with("image/"))
+                .map(|_| item.link.clone());
+
+            self.feed.items.push(JsonFeedItem {
+                id: item.guid,
+                url: item.link,
+                title: item.title,
+                date_published: fmt_rfc3339(item.pub_time),
+                summary,
+                content_html,
+                content_text,
+                tags: item.tags,
+                image,
+            });
+        }
+    }
+
+    fn write_search_items(&mut self, _: &SearchQuery, items: Vec<FeedItem>) {
+        self.write_feed_items(items);
+    }
+
+    fn write_pagination(&mut self, pagination: &Pagination) {
+        self.feed.next_url = pagination
+            .next
+            .as_ref()
+            .and_then(|query| page_link(&self.feed.home_page_url, &self.path, query))
+            .map(String::from);
+    }
+
+    fn finish(self) -> String {
+        serde_json::to_string(&self.feed).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::DocumentKind;
+    use crate::FeedItem;
+    use crate::FeedWriter;
+    use crate::Note;
+    use crate::NoteKind;
+    use crate::json::JsonFeedWriter;
+    use std::time::SystemTime;
+
+    #[test]
+    fn json_feed_items() {
+        let mut writer = JsonFeedWriter::new(
+            "My Paket",
+            "My links",
+            "https://paket.example/",
+            SystemTime::UNIX_EPOCH,
+        );
+        writer.write_feed_items(vec![
+            FeedItem {
+                title: "AT&T \"<3\" you".to_string(),
+                link: "https://example.com/".to_string(),
+                pub_date: String::new(),
+                expire_date: "Thu, 01 Jan 1970 00:00:00 GMT".to_string(),
+                pub_time: SystemTime::UNIX_EPOCH,
+                guid: "guid".to_string(),
+                kind: DocumentKind::Html,
+                tags: vec!["rust".to_string()],
+                notes: Vec::new(),
+            },
+            FeedItem {
+                title: "Chart".to_string(),
+                link: "https://example.com/chart.PNG".to_string(),
+                pub_date: String::new(),
+                expire_date: String::new(),
+                pub_time: SystemTime::UNIX_EPOCH,
+                guid: "chart".to_string(),
+                kind: DocumentKind::Html,
+                tags: Vec::new(),
+                notes: vec![Note {
+                    id: 1,
+                    kind: NoteKind::Highlight,
+                    text: "up & right".to_string(),
+                }],
+            },
+        ]);
+
+        let feed: serde_json::Value = serde_json::from_str(&writer.finish()).unwrap();
+
+        assert_eq!(feed["version"], "https://jsonfeed.org/version/1.1");
+        assert_eq!(feed["feed_url"], "https://paket.example/feed.json");
+
+        let item = &feed["items"][0];
+        assert_eq!(item["id"], "guid");
+        assert_eq!(item["title"], "AT&T \"<3\" you");
+        assert_eq!(item["date_published"], "1970-01-01T00:00:00Z");
+        assert_eq!(item["tags"][0], "rust");
+        assert_eq!(item["summary"], "Expires: Thu, 01 Jan 1970 00:00:00 GMT");
+        assert_eq!(
+            item["content_text"],
+            "Expires: Thu, 01 Jan 1970 00:00:00 GMT"
+        );
+        assert!(item.get("image").is_none());
+
+        for item in feed["items"].as_array().unwrap() {
+            assert!(item.get("content_html").is_some() || item.get("content_text").is_some());
+        }
+
+        let chart = &feed["items"][1];
+        assert_eq!(chart["image"], "https://example.com/chart.PNG");
+        assert_eq!(
+            chart["content_html"],
+            "<blockquote>up &amp; right</blockquote>"
+        );
+    }
+}
@@ -1,44 +1,116 @@
+use anyhow::bail;
 use argh::FromArgs;
+use axum::Extension;
 use axum::Form;
+use axum::Json;
 use axum::Router;
+use axum::extract::Path;
+use axum::extract::Query;
 use axum::extract::State;
+use axum::http::HeaderMap;
 use axum::http::response::Response;
 use axum::http::status::StatusCode;
+use axum::middleware::from_fn_with_state;
 use axum::response::Redirect;
+use axum::routing::delete;
 use axum::routing::get;
+use axum::routing::patch;
 use axum::routing::post;
 use axum::routing::put;
 use axum::serve::ListenerExt;
 use core::net::Ipv4Addr;
 use duckdb::Connection;
+use duckdb::OptionalExt;
 use duckdb::Transaction;
 use duckdb::params;
 use http::init_tls_certs;
 use serde::Deserialize;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
 use std::sync::Arc;
 use std::sync::Mutex;
 use std::time::Duration;
 use std::time::SystemTime;
 use tokio::net::TcpListener;
 use tokio::time::timeout;
+use tower_http::compression::CompressionLayer;
+use tower_http::compression::predicate::SizeAbove;
 use url::Url;
+use url::form_urlencoded;
 use uuid::Uuid;
 
+use crate::atom::AtomWriter;
+use crate::auth::AuthMode;
+use crate::auth::BasicAuth;
+use crate::auth::FeedTokenCommand;
+use crate::auth::QueryToken;
+use crate::auth::TokenCommand;
+use crate::auth::parse_auth_mode;
+use crate::auth::parse_basic_auth;
+use crate::cache::FeedCache;
+use crate::cache::Validators;
+use crate::collection::CollectionCommand;
+use crate::cors::CorsHeaders;
+use crate::cors::CorsMethods;
+use crate::cors::CorsOrigins;
+use crate::cors::parse_cors_headers;
+use crate::cors::parse_cors_methods;
+use crate::cors::parse_cors_origins;
+use crate::csrf::Csrf;
+use crate::csrf::CsrfToken;
+use crate::extend::ExtendKey;
+use crate::filter::FeedFilter;
+use crate::filter::ReadState;
+use crate::filter::SortOrder;
 use crate::html::HtmlWriter;
 use crate::http::Document;
 use crate::http::PlainOrTls;
 use crate::http::request_document;
+use crate::json::JsonFeedWriter;
+use crate::markup::Markup;
+use crate::page::Cursor;
+use crate::page::Page;
+use crate::page::PageQuery;
+use crate::page::Pagination;
 use crate::rss::RssWriter;
+use crate::search::SearchQuery;
+use crate::stats::Stats;
+use crate::stats::fetch_stats;
+use crate::user::User;
+use crate::user::UserCommand;
 
 use log::error;
 use log::info;
 
+mod api;
+mod atom;
+mod auth;
+mod cache;
+mod collection;
+mod cors;
+mod csrf;
+mod extend;
+mod filter;
 mod html;
 mod http;
+mod install;
+mod json;
+mod markup;
+mod page;
 mod rss;
+mod search;
+mod stats;
+mod user;
 
 type DbConnection = Arc<Mutex<Connection>>;
 
+const PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Readers may keep a feed but have to revalidate it with `ETag` or `Last-Modified`
+const FEED_CACHE_CONTROL: &str = "no-cache";
+
 /// Paket: read before it goes away
 #[derive(Debug, Clone, FromArgs)]
 #[argh(help_triggers("-h", "--help"))]
@@ -66,6 +138,106 @@ struct Args {
     /// time to live in days
     #[argh(option, default = "60")]
     ttl: u32,
+
+    /// days before expiry an article is shown as expiring
+    #[argh(option, default = "7")]
+    expiring_window: u32,
+
+    /// days a deleted article is kept in trash
+    #[argh(option, default = "14")]
+    trash_grace: u32,
+
+    /// articles per page of the HTML feed
+    #[argh(option, default = "50")]
+    page_size: u32,
+
+    /// articles in RSS, Atom and JSON feeds unless a limit is asked for
+    #[argh(option, default = "100")]
+    feed_limit: u32,
+
+    /// response compression, comma separated gzip, br, zstd or none
+    #[argh(option, default = "Compression::ALL", from_str_fn(parse_compression))]
+    compression: Compression,
+
+    /// smallest response in bytes worth compressing
+    #[argh(option, default = "1024")]
+    compression_min_size: u16,
+
+    /// requests needing a token or basic credentials: none, writes or all
+    #[argh(option, default = "AuthMode::None", from_str_fn(parse_auth_mode))]
+    auth: AuthMode,
+
+    /// user:password for HTTP Basic auth in browsers
+    #[argh(option, from_str_fn(parse_basic_auth))]
+    basic_auth: Option<BasicAuth>,
+
+    /// origins browsers may call from, comma separated or * for any
+    #[argh(option, from_str_fn(parse_cors_origins))]
+    cors_origins: Option<CorsOrigins>,
+
+    /// methods allowed from other origins, comma separated
+    #[argh(
+        option,
+        default = "parse_cors_methods(\"GET,POST,PUT,PATCH,DELETE\").unwrap()",
+        from_str_fn(parse_cors_methods)
+    )]
+    cors_methods: CorsMethods,
+
+    /// request headers allowed from other origins, comma separated
+    #[argh(
+        option,
+        default = "parse_cors_headers(\"authorization,content-type\").unwrap()",
+        from_str_fn(parse_cors_headers)
+    )]
+    cors_headers: CorsHeaders,
+
+    #[argh(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Debug, Clone, FromArgs)]
+#[argh(subcommand)]
+enum Command {
+    User(UserCommand),
+    Collection(CollectionCommand),
+    Token(TokenCommand),
+    FeedToken(FeedTokenCommand),
+}
+
+/// Encodings offered to clients through `Accept-Encoding`
+#[derive(Debug, Clone, Copy)]
+struct Compression {
+    gzip: bool,
+    br: bool,
+    zstd: bool,
+}
+
+impl Compression {
+    const ALL: Self = Self {
+        gzip: true,
+        br: true,
+        zstd: true,
+    };
+}
+
+fn parse_compression(encodings: &str) -> Result<Compression, String> {
+    let mut compression = Compression {
+        gzip: false,
+        br: false,
+        zstd: false,
+    };
+
+    for encoding in encodings.split(',').map(str::trim) {
+        match encoding {
+            "gzip" => compression.gzip = true,
+            "br" => compression.br = true,
+            "zstd" => compression.zstd = true,
+            "none" => {}
+            _ => return Err(format!("unknown compression {encoding}")),
+        }
+    }
+
+    Ok(compression)
 }
 
 fn parse_http_url(url_str: &str) -> Result<String, String> {
@@ -83,6 +255,9 @@ fn parse_http_url(url_str: &str) -> Result<String, String> {
 struct App {
     args: Arc<Args>,
     db_connection: DbConnection,
+    feed_cache: Arc<Mutex<FeedCache>>,
+    csrf: Arc<Csrf>,
+    extend_key: Arc<ExtendKey>,
 }
 
 fn main() -> anyhow::Result<()> {
@@ -91,6 +266,21 @@ fn main() -> anyhow::Result<()> {
 
     env_logger::init();
 
+    if let Some(command) = &args.command {
+        let mut db_connection = Connection::open(&args.db)?;
+        setup_tables(&mut db_connection)?;
+        return match command {
+            Command::User(command) => user::run_user_command(&mut db_connection, command, &args),
+            Command::Collection(command) => {
+                collection::run_collection_command(&mut db_connection, command, &args)
+            }
+            Command::Token(command) => auth::run_token_command(&mut db_connection, command),
+            Command::FeedToken(command) => {
+                auth::run_feed_token_command(&mut db_connection, command, args.link.as_str())
+            }
+        };
+    }
+
     let runtime = tokio::runtime::Builder::new_current_thread()
         .enable_io()
         .enable_time()
@@ -106,8 +296,11 @@ async fn serve(args: Arc<Args>) -> anyhow::Result<()> {
     let mut db_connection = Connection::open(&args.db)?;
 
     setup_tables(&mut db_connection)?;
+    let extend_key = ExtendKey::load(&mut db_connection)?;
     let db_connection = Arc::new(Mutex::new(db_connection));
 
+    tokio::spawn(purge_job(args.clone(), db_connection.clone()));
+
     let port = args.port;
     let tcp_listener = TcpListener::bind((Ipv4Addr::UNSPECIFIED, port))
         .await?
@@ -115,15 +308,124 @@ async fn serve(args: Arc<Args>) -> anyhow::Result<()> {
             let _ = tcp_stream.set_nodelay(true);
         });
 
-    let router = Router::new()
-        .route("/save", put(handle_save_article))
+    let app = App {
+        args: args.clone(),
+        db_connection,
+        feed_cache: Arc::new(Mutex::new(FeedCache::default())),
+        csrf: Arc::new(Csrf::new()?),
+        extend_key: Arc::new(extend_key),
+    };
+
+    let forms = Router::new()
         .route("/delete", post(handle_delete_article))
+        .route("/restore", post(handle_restore_article))
+        .route("/edit", post(handle_edit_article))
+        .route("/refetch", post(handle_refetch_article))
+        .route("/note", post(handle_add_note))
+        .route("/note/edit", post(handle_edit_note))
+        .route("/note/delete", post(handle_delete_note))
+        .route("/extend", post(handle_extend_article))
+        .route("/install", post(install::handle_create_bookmarklet))
+        .route_layer(from_fn_with_state(app.clone(), csrf::require_csrf_token));
+
+    let writes = Router::new()
+        .merge(forms)
+        .route("/save", put(handle_save_article))
+        .route(
+            "/add",
+            get(handle_add_article).route_layer(from_fn_with_state(
+                app.clone(),
+                csrf::require_own_navigation,
+            )),
+        )
+        .route(
+            "/share",
+            get(install::handle_share).route_layer(from_fn_with_state(
+                app.clone(),
+                csrf::require_own_navigation,
+            )),
+        )
+        .route("/read", post(handle_read_article))
+        .route("/article", patch(handle_patch_article))
+        .route("/api/v1/articles", post(api::handle_create_article))
+        .route("/api/v1/articles/batch", post(api::handle_batch_save))
+        .route("/api/v1/articles/bulk", post(api::handle_bulk_update))
+        .route(
+            "/api/v1/collections/{name}/articles",
+            post(api::handle_create_collection_article),
+        )
+        .route(
+            "/api/v1/collections/{name}/articles/{guid}",
+            delete(api::handle_delete_collection_article),
+        )
+        .route(
+            "/api/v1/articles/{guid}",
+            patch(api::handle_update_article).delete(api::handle_delete_article),
+        )
+        .route_layer(from_fn_with_state(app.clone(), auth::require_user));
+
+    let feeds = Router::new()
         .route("/feed.xml", get(handle_get_feed_xml))
+        .route("/feed.atom", get(handle_get_feed_atom))
+        .route("/feed.json", get(handle_get_feed_json))
+        .route("/expiring.xml", get(handle_get_expiring_xml))
+        .route("/search.xml", get(handle_search_xml))
+        .route_layer(from_fn_with_state(app.clone(), auth::require_user));
+
+    let pages = Router::new()
         .route("/feed.html", get(handle_get_feed_html))
-        .with_state(App {
-            args: args.clone(),
-            db_connection,
-        });
+        .route("/trash.html", get(handle_get_trash_html))
+        .route("/install", get(install::handle_get_install))
+        .route_layer(from_fn_with_state(app.clone(), csrf::issue_csrf_token));
+
+    let reads = Router::new()
+        .merge(pages)
+        .route("/stats", get(handle_get_stats_html))
+        .route("/api/stats", get(handle_get_stats_json))
+        .route("/search", get(handle_search_html))
+        .route("/api/v1/articles", get(api::handle_list_articles))
+        .route("/api/v1/articles/{guid}", get(api::handle_get_article))
+        .route(
+            "/api/v1/collections/{name}/articles",
+            get(api::handle_list_collection_articles),
+        )
+        .route_layer(from_fn_with_state(app.clone(), auth::require_reader));
+
+    let collections = Router::new()
+        .route(
+            "/collections/{name}/feed.html",
+            get(collection::handle_get_collection_html),
+        )
+        .route(
+            "/collections/{name}/feed.xml",
+            get(collection::handle_get_collection_xml),
+        )
+        .route_layer(from_fn_with_state(app.clone(), auth::identify_user));
+
+    let router = writes
+        .merge(feeds)
+        .merge(reads)
+        .merge(collections)
+        .route("/manifest.webmanifest", get(install::handle_get_manifest))
+        .route("/app.js", get(install::handle_get_app))
+        .route("/sw.js", get(install::handle_get_service_worker))
+        .route("/icon.svg", get(install::handle_get_icon))
+        .route("/feed/{file}", get(handle_get_token_feed))
+        .route("/extend", get(handle_follow_extend_link))
+        .layer(
+            CompressionLayer::new()
+                .gzip(args.compression.gzip)
+                .br(args.compression.br)
+                .zstd(args.compression.zstd)
+                .compress_when(SizeAbove::new(args.compression_min_size)),
+        )
+        .with_state(app);
+
+    // outermost, so that preflight requests are answered before auth
+    let router = match cors::cors_layer(&args) {
+        Some(cors_layer) => router.layer(cors_layer),
+        None => router,
+    };
 
     info!("Serving {args:?}");
     axum::serve(tcp_listener, router).await?;
@@ -131,10 +433,99 @@ async fn serve(args: Arc<Args>) -> anyhow::Result<()> {
     Ok(())
 }
 
-async fn handle_save_article(State(state): State<App>, Form(save): Form<SaveForm>) -> StatusCode {
-    info!("save_article: {save:?}");
+/// Expires old articles of every user and collection and empties the trash
+/// even when no feed is requested
+async fn purge_job(args: Arc<Args>, db_connection: DbConnection) {
+    let mut interval = tokio::time::interval(PURGE_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        let mut db_lock = db_connection.lock().unwrap();
+
+        let result = user::fetch_users(&mut db_lock, &args)
+            .and_then(|users| {
+                users
+                    .iter()
+                    .try_for_each(|user| expire_old_articles(&mut db_lock, user))
+            })
+            .and_then(|_| purge_trash(&mut db_lock, &args))
+            .and_then(|_| collection::expire_collection_articles(&mut db_lock, &args));
+
+        if let Err(err) = result {
+            error!("{err}");
+        }
+    }
+}
+
+async fn handle_save_article(
+    State(state): State<App>,
+    Extension(user): Extension<User>,
+    Form(save): Form<SaveForm>,
+) -> StatusCode {
+    info!("save_article: {} {save:?}", user.name);
+
+    save_article(state, &user, &save).await
+}
+
+/// `GET /add?url=...` for bookmarklets. Sends the reader back to the page with
+/// `redirect=back` when the bookmarklet's token was used, to the feed otherwise
+async fn handle_add_article(
+    State(state): State<App>,
+    Extension(user): Extension<User>,
+    query_token: Option<Extension<QueryToken>>,
+    Query(save): Query<SaveForm>,
+    Query(add): Query<AddQuery>,
+) -> Result<Redirect, StatusCode> {
+    info!("add_article: {} {save:?} {add:?}", user.name);
+
+    // only a link which can be saved is followed back
+    let back = parse_http_url(&save.url).map_err(|_| StatusCode::BAD_REQUEST)?;
+
+    let status = save_article(state, &user, &save).await;
+    if status != StatusCode::OK {
+        return Err(status);
+    }
+
+    match (add.redirect, query_token) {
+        (Some(AddRedirect::Back), Some(_)) => Ok(Redirect::to(&back)),
+        _ => Ok(Redirect::to("/feed.html")),
+    }
+}
+
+/// Saves into the user's own list or into one of their collections
+async fn save_article(state: App, user: &User, save: &SaveForm) -> StatusCode {
+    if let Some(name) = &save.collection {
+        let collection = {
+            let mut db_lock = state.db_connection.lock().unwrap();
+            collection::fetch_member_collection(&mut db_lock, name, user, &state.args)
+        };
+
+        let result = match collection {
+            Ok(Some(collection)) => {
+                collection::add_to_collection(
+                    &save.url,
+                    save.title.as_deref(),
+                    &collection,
+                    user,
+                    state.db_connection,
+                )
+                .await
+            }
+            Ok(None) => return StatusCode::NOT_FOUND,
+            Err(err) => Err(err),
+        };
+
+        if let Err(err) = result {
+            error!("{err}");
+            return StatusCode::INTERNAL_SERVER_ERROR;
+        }
+
+        return StatusCode::OK;
+    }
 
-    if let Err(err) = add_article(&save.url, state.db_connection).await {
+    if let Err(err) = add_article(&save.url, save.title.as_deref(), user, state.db_connection).await
+    {
         error!("{err}");
         return StatusCode::INTERNAL_SERVER_ERROR;
     }
@@ -144,41 +535,150 @@ async fn handle_save_article(State(state): State<App>, Form(save): Form<SaveForm
 
 async fn handle_delete_article(
     State(state): State<App>,
+    Extension(user): Extension<User>,
     Form(delete): Form<DeleteForm>,
 ) -> Redirect {
-    info!("delete_article: {delete:?}");
+    info!("delete_article: {} {delete:?}", user.name);
+
+    let mut db_lock = state.db_connection.lock().unwrap();
+
+    if let Err(err) = delete_article(&mut db_lock, &user, &delete.guid) {
+        error!("{err}");
+        return Redirect::to("/feed.html");
+    }
+
+    let query = form_urlencoded::Serializer::new(String::new())
+        .append_pair("undo", &delete.guid)
+        .finish();
+    Redirect::to(&format!("/feed.html?{query}"))
+}
+
+async fn handle_restore_article(
+    State(state): State<App>,
+    Extension(user): Extension<User>,
+    Form(restore): Form<RestoreForm>,
+) -> Redirect {
+    info!("restore_article: {} {restore:?}", user.name);
+
+    let mut db_lock = state.db_connection.lock().unwrap();
+
+    if let Err(err) = restore_article(&mut db_lock, &user, &restore.guid) {
+        error!("{err}");
+    }
+
+    Redirect::to("/feed.html")
+}
+
+async fn handle_patch_article(
+    State(state): State<App>,
+    Extension(user): Extension<User>,
+    Form(edit): Form<EditForm>,
+) -> StatusCode {
+    info!("patch_article: {} {edit:?}", user.name);
+
+    let mut db_lock = state.db_connection.lock().unwrap();
+
+    if let Err(err) = edit_article(&mut db_lock, &user, &edit) {
+        error!("{err}");
+        return StatusCode::INTERNAL_SERVER_ERROR;
+    }
+
+    StatusCode::OK
+}
+
+async fn handle_edit_article(
+    State(state): State<App>,
+    Extension(user): Extension<User>,
+    Form(edit): Form<EditForm>,
+) -> Redirect {
+    info!("edit_article: {} {edit:?}", user.name);
+
+    let mut db_lock = state.db_connection.lock().unwrap();
+
+    if let Err(err) = edit_article(&mut db_lock, &user, &edit) {
+        error!("{err}");
+    }
+
+    Redirect::to("/feed.html")
+}
+
+async fn handle_refetch_article(
+    State(state): State<App>,
+    Extension(user): Extension<User>,
+    Form(refetch): Form<RefetchForm>,
+) -> Redirect {
+    info!("refetch_article: {} {refetch:?}", user.name);
+
+    if let Err(err) = refetch_article(&refetch.guid, &user, state.db_connection).await {
+        error!("{err}");
+    }
+
+    Redirect::to("/feed.html")
+}
+
+async fn handle_add_note(
+    State(state): State<App>,
+    Extension(user): Extension<User>,
+    Form(note): Form<NoteForm>,
+) -> Redirect {
+    info!("add_note: {} {note:?}", user.name);
 
     let mut db_lock = state.db_connection.lock().unwrap();
 
-    if let Err(err) = delete_article(&mut db_lock, &delete.guid) {
+    if let Err(err) = add_note(&mut db_lock, &user, &note) {
         error!("{err}");
     }
 
     Redirect::to("/feed.html")
 }
 
-async fn handle_get_feed_xml(State(state): State<App>) -> Response<String> {
-    handle_get_feed::<RssWriter>(state).await
+async fn handle_edit_note(
+    State(state): State<App>,
+    Extension(user): Extension<User>,
+    Form(edit): Form<EditNoteForm>,
+) -> Redirect {
+    info!("edit_note: {} {edit:?}", user.name);
+
+    let mut db_lock = state.db_connection.lock().unwrap();
+
+    if let Err(err) = edit_note(&mut db_lock, &user, edit.id, &edit.text) {
+        error!("{err}");
+    }
+
+    Redirect::to("/feed.html")
 }
 
-async fn handle_get_feed_html(State(state): State<App>) -> Response<String> {
-    handle_get_feed::<HtmlWriter>(state).await
+async fn handle_delete_note(
+    State(state): State<App>,
+    Extension(user): Extension<User>,
+    Form(delete): Form<DeleteNoteForm>,
+) -> Redirect {
+    info!("delete_note: {} {delete:?}", user.name);
+
+    let mut db_lock = state.db_connection.lock().unwrap();
+
+    if let Err(err) = delete_note(&mut db_lock, &user, delete.id) {
+        error!("{err}");
+    }
+
+    Redirect::to("/feed.html")
 }
 
-async fn handle_get_feed<T: FeedWriter>(state: App) -> Response<String> {
-    info!("get_feed");
+async fn handle_get_trash_html(
+    State(state): State<App>,
+    Extension(user): Extension<User>,
+    Extension(csrf_token): Extension<CsrfToken>,
+) -> Response<String> {
+    info!("get_trash: {}", user.name);
 
     let result = {
         let mut db_lock = state.db_connection.lock().unwrap();
 
-        delete_old_articles(&mut db_lock, &state.args)
-            .and_then(|_| fetch_feed(&mut db_lock))
-            .and_then(|feed_items| {
-                fetch_weekly_stats(&mut db_lock).map(|weekly_items| (feed_items, weekly_items))
-            })
+        purge_trash(&mut db_lock, &state.args)
+            .and_then(|_| fetch_trash(&mut db_lock, &user, &state.args))
     };
 
-    let (feed_items, weekly_items) = match result {
+    let trash_items = match result {
         Ok(items) => items,
         Err(err) => {
             error!("{err}");
@@ -189,164 +689,1798 @@ async fn handle_get_feed<T: FeedWriter>(state: App) -> Response<String> {
         }
     };
 
-    let feed = build_feed::<T>(feed_items, weekly_items, &state.args);
+    let title = format!("{}: trash", user.feed_name);
+    let mut writer = HtmlWriter::new(
+        &title,
+        &user.feed_desc,
+        state.args.link.as_str(),
+        SystemTime::now(),
+    );
+    writer.set_csrf_token(&csrf_token.0);
+    writer.write_trash_items(trash_items);
+    let page = writer.finish();
 
     Response::builder()
         .status(StatusCode::OK)
-        .header("Content-Type", T::CONTENT_TYPE)
-        .body(feed)
+        .header("Content-Type", HtmlWriter::CONTENT_TYPE)
+        .body(page)
         .unwrap()
 }
 
-#[derive(Debug, Deserialize)]
-struct DeleteForm {
-    guid: String,
-}
+/// Marks an article as read, pinged by the browser when its link is followed
+async fn handle_read_article(
+    State(state): State<App>,
+    Extension(user): Extension<User>,
+    Query(read): Query<ReadQuery>,
+) -> StatusCode {
+    info!("read_article: {} {read:?}", user.name);
 
-#[derive(Debug, Deserialize)]
-struct SaveForm {
-    url: String,
-}
+    let mut db_lock = state.db_connection.lock().unwrap();
 
-#[derive(Debug)]
-struct Article {
-    url: Url,
-    title: String,
+    match read_article(&mut db_lock, &user, &read.guid) {
+        Ok(true) => StatusCode::NO_CONTENT,
+        Ok(false) => StatusCode::NOT_FOUND,
+        Err(err) => {
+            error!("{err}");
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
 }
 
-struct FeedItem {
-    title: String,
-    link: String,
-    pub_date: String,
-    guid: String,
-}
+async fn handle_extend_article(
+    State(state): State<App>,
+    Extension(user): Extension<User>,
+    Query(extend): Query<ExtendQuery>,
+) -> Redirect {
+    info!("extend_article: {} {extend:?}", user.name);
 
-struct WeeklyItem {
-    articles_count: i64,
+    let mut db_lock = state.db_connection.lock().unwrap();
+
+    if let Err(err) = extend_article(&mut db_lock, &user, &extend.guid) {
+        error!("{err}");
+    }
+
+    Redirect::to("/feed.html")
 }
 
-async fn add_article(url: &str, db_connection: DbConnection) -> anyhow::Result<()> {
-    let fetch_and_extract = async {
-        let document = request_document(url).await?;
-        extract_article(document).await
-    };
+/// Extend link of a feed, only followed with the token signing its guid. Feed readers
+/// don't send credentials with links, the token stands in for them and the article
+/// is extended on behalf of its owner
+async fn handle_follow_extend_link(
+    State(state): State<App>,
+    Query(extend): Query<ExtendLinkQuery>,
+) -> Result<Redirect, StatusCode> {
+    info!("follow_extend_link: {}", extend.guid);
 
-    let article = timeout(Duration::from_secs(5), fetch_and_extract).await??;
+    if !state.extend_key.verify(&extend.guid, &extend.token) {
+        return Err(StatusCode::FORBIDDEN);
+    }
 
-    let mut db_lock = db_connection.lock().unwrap();
-    store_article(&mut db_lock, article)?;
+    let mut db_lock = state.db_connection.lock().unwrap();
 
-    Ok(())
-}
+    let owner = db_lock
+        .query_row(
+            "SELECT user_id FROM articles WHERE guid = ? AND deleted_at IS NULL",
+            [&extend.guid],
+            |row| row.get::<_, i64>(0),
+        )
+        .optional()
+        .map_err(anyhow::Error::from)
+        .and_then(|user_id| match user_id {
+            Some(user_id) => user::fetch_user(&mut db_lock, user_id, &state.args),
+            None => Ok(None),
+        });
 
-async fn extract_article(document: Document<PlainOrTls>) -> anyhow::Result<Article> {
-    let (url, title) = match document {
-        Document::Unsupported(url) => {
-            let title = format!("[???] {url}");
-            (url, title)
-        }
-        Document::Pdf(url) => {
-            let title = url
-                .path_segments()
-                .and_then(|mut segments| segments.next_back())
-                .map(ToString::to_string)
-                .unwrap_or_else(|| url.to_string());
-            let title = format!("[PDF] {title}");
-            (url, title)
-        }
-        Document::Html(url, mut http_body_reader) => {
-            let title = http_body_reader.extract_title().await?;
-            let title = title.unwrap_or_else(|| "[NO TITLE]".to_string());
-            (url, title)
+    let user = match owner {
+        Ok(Some(user)) => user,
+        Ok(None) => return Err(StatusCode::NOT_FOUND),
+        Err(err) => {
+            error!("{err}");
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
         }
     };
 
-    Ok(Article { url, title })
+    if let Err(err) = extend_article(&mut db_lock, &user, &extend.guid) {
+        error!("{err}");
+    }
+
+    Ok(Redirect::to("/feed.html"))
 }
 
-fn setup_tables(db_connection: &mut Connection) -> anyhow::Result<()> {
-    db_connection.execute_batch(
-        "
-        CREATE TABLE IF NOT EXISTS articles (
-            timestamp TIMESTAMP WITH TIME ZONE NOT NULL,
-            title TEXT NOT NULL,
-            link TEXT NOT NULL,
-            guid TEXT NOT NULL);
-        CREATE TABLE IF NOT EXISTS stats_per_week_of_year (
-            week_of_year INT64 NOT NULL PRIMARY KEY,
-            articles_count INT64 NOT NULL);",
-    )?;
+async fn handle_get_expiring_xml(
+    State(state): State<App>,
+    Extension(user): Extension<User>,
+) -> Response<String> {
+    info!("get_expiring: {}", user.name);
 
-    Ok(())
-}
+    let result = {
+        let mut db_lock = state.db_connection.lock().unwrap();
 
-fn store_article(db_connection: &mut Connection, article: Article) -> anyhow::Result<()> {
-    let uuid = Uuid::new_v5(&Uuid::NAMESPACE_URL, article.url.as_str().as_bytes());
-    let guid = uuid.to_string();
+        expire_old_articles(&mut db_lock, &user)
+            .and_then(|_| fetch_expiring_feed(&mut db_lock, &user, &state.args))
+    };
 
-    let tx = db_connection.transaction()?;
-    tx.execute("DELETE FROM articles WHERE guid = ?", [&guid])?;
-    tx.execute(
-        "INSERT INTO articles 
-        (title, link, guid, timestamp)
-        VALUES
-        (?, ?, ?, current_timestamp)",
-        params![article.title, article.url.as_str(), &guid],
-    )?;
-    update_weekly_stats(&tx)?;
-    tx.commit()?;
+    let expiring_items = match result {
+        Ok(items) => items,
+        Err(err) => {
+            error!("{err}");
+            return Response::builder()
+                .status(StatusCode::INTERNAL_SERVER_ERROR)
+                .body(String::new())
+                .unwrap();
+        }
+    };
 
-    Ok(())
+    let title = format!("{}: expiring soon", user.feed_name);
+    let mut writer = RssWriter::new(
+        &title,
+        &user.feed_desc,
+        state.args.link.as_str(),
+        SystemTime::now(),
+    );
+    writer.set_extend_key(&state.extend_key);
+    writer.set_author(&user.name);
+    writer.write_feed_items(expiring_items);
+    let feed = writer.finish();
+
+    Response::builder()
+        .status(StatusCode::OK)
+        .header("Content-Type", RssWriter::CONTENT_TYPE)
+        .body(feed)
+        .unwrap()
 }
 
-fn delete_article(db_connection: &mut Connection, guid: &str) -> anyhow::Result<()> {
-    let tx = db_connection.transaction()?;
-    tx.execute("DELETE FROM articles WHERE guid = ?", [guid])?;
-    update_weekly_stats(&tx)?;
-    tx.commit()?;
-    Ok(())
+async fn handle_get_feed_xml(
+    State(state): State<App>,
+    Extension(user): Extension<User>,
+    Query(page): Query<PageQuery>,
+    Query(filter): Query<FeedFilter>,
+    headers: HeaderMap,
+) -> Response<String> {
+    handle_get_cached_feed::<RssWriter>(state, user, RssWriter::PATH, page, filter, headers).await
 }
 
-fn delete_old_articles(db_connection: &mut Connection, args: &Args) -> anyhow::Result<()> {
-    let tx = db_connection.transaction()?;
-    tx.execute(
-        "DELETE FROM articles WHERE (current_timestamp AT TIME ZONE 'UTC' - timestamp AT TIME ZONE 'UTC') > INTERVAL (?) DAY",
-        [args.ttl],
-    )?;
-    update_weekly_stats(&tx)?;
-    tx.commit()?;
-    Ok(())
+async fn handle_get_feed_html(
+    State(state): State<App>,
+    Extension(user): Extension<User>,
+    Query(query): Query<FeedQuery>,
+    Query(page): Query<PageQuery>,
+    Query(filter): Query<FeedFilter>,
+    Extension(csrf_token): Extension<CsrfToken>,
+) -> Response<String> {
+    handle_get_feed::<HtmlWriter>(state, user, query, page, filter, csrf_token).await
 }
 
-fn update_weekly_stats(tx: &Transaction) -> anyhow::Result<()> {
-    tx.execute_batch(
-        "
-        INSERT OR REPLACE INTO stats_per_week_of_year (week_of_year, articles_count) 
-        VALUES (weekofyear(current_timestamp), (SELECT count(*) FROM articles));
+async fn handle_get_feed_atom(
+    State(state): State<App>,
+    Extension(user): Extension<User>,
+    Query(page): Query<PageQuery>,
+    Query(filter): Query<FeedFilter>,
+    headers: HeaderMap,
+) -> Response<String> {
+    handle_get_cached_feed::<AtomWriter>(state, user, AtomWriter::PATH, page, filter, headers).await
+}
 
-        DELETE FROM stats_per_week_of_year WHERE week_of_year > weekofyear(current_timestamp)",
-    )?;
-    Ok(())
+async fn handle_get_feed_json(
+    State(state): State<App>,
+    Extension(user): Extension<User>,
+    Query(page): Query<PageQuery>,
+    Query(filter): Query<FeedFilter>,
+    headers: HeaderMap,
+) -> Response<String> {
+    handle_get_cached_feed::<JsonFeedWriter>(
+        state,
+        user,
+        JsonFeedWriter::PATH,
+        page,
+        filter,
+        headers,
+    )
+    .await
 }
 
-fn fetch_feed(db_connection: &mut Connection) -> anyhow::Result<Vec<FeedItem>> {
-    let mut select_stmt = db_connection.prepare(
-        "SELECT 
-        title, link, guid, strftime(timestamp AT TIME ZONE 'GMT', '%a, %d %b %Y %X GMT') 
-        FROM articles
-        ORDER BY timestamp DESC",
-    )?;
+/// `/feed/<token>.xml`, `.atom` or `.json` for readers which can't send credentials.
+/// The feed is the token's user's and the token's scope narrows the filter
+async fn handle_get_token_feed(
+    State(state): State<App>,
+    Path(file): Path<String>,
+    Query(page): Query<PageQuery>,
+    Query(filter): Query<FeedFilter>,
+    headers: HeaderMap,
+) -> Response<String> {
+    let not_found = || {
+        Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .body(String::new())
+            .unwrap()
+    };
 
-    let mut rows = select_stmt.query([])?;
-    let count = rows.as_ref().unwrap().row_count();
+    let Some((token, extension)) = file
+        .rsplit_once('.')
+        .filter(|(_, extension)| matches!(*extension, "xml" | "atom" | "json"))
+    else {
+        return not_found();
+    };
+
+    let verified = {
+        let mut db_lock = state.db_connection.lock().unwrap();
+        auth::verify_feed_token(&mut db_lock, token).and_then(|verified| match verified {
+            Some((user_id, scope)) => {
+                Ok(user::fetch_user(&mut db_lock, user_id, &state.args)?.map(|user| (user, scope)))
+            }
+            None => Ok(None),
+        })
+    };
+
+    let (user, scope) = match verified {
+        Ok(Some(verified)) => verified,
+        Ok(None) => return not_found(),
+        Err(err) => {
+            error!("{err}");
+            return Response::builder()
+                .status(StatusCode::INTERNAL_SERVER_ERROR)
+                .body(String::new())
+                .unwrap();
+        }
+    };
+
+    let path = format!("feed/{file}");
+    let filter = scope.narrow(filter);
+
+    match extension {
+        "xml" => {
+            handle_get_cached_feed::<RssWriter>(state, user, &path, page, filter, headers).await
+        }
+        "atom" => {
+            handle_get_cached_feed::<AtomWriter>(state, user, &path, page, filter, headers).await
+        }
+        "json" => {
+            handle_get_cached_feed::<JsonFeedWriter>(state, user, &path, page, filter, headers)
+                .await
+        }
+        _ => not_found(),
+    }
+}
+
+async fn handle_get_feed<T: FeedWriter>(
+    state: App,
+    user: User,
+    query: FeedQuery,
+    page: PageQuery,
+    filter: FeedFilter,
+    csrf_token: CsrfToken,
+) -> Response<String> {
+    info!("get_feed: {} {page:?} {filter:?}", user.name);
+
+    let page = Page::new(&page, state.args.page_size);
+
+    let result = {
+        let mut db_lock = state.db_connection.lock().unwrap();
+
+        expire_old_articles(&mut db_lock, &user)
+            .and_then(|_| fetch_feed_page(&mut db_lock, &user, &page, &filter))
+            .and_then(|feed_page| {
+                fetch_expiring_feed(&mut db_lock, &user, &state.args)
+                    .map(|expiring_items| (feed_page, expiring_items))
+            })
+            .and_then(|(feed_page, expiring_items)| {
+                fetch_weekly_stats(&mut db_lock, &user)
+                    .map(|weekly_items| (feed_page, expiring_items, weekly_items))
+            })
+            .and_then(|(feed_page, expiring_items, weekly_items)| {
+                fetch_trashed_guid(&mut db_lock, &user, query.undo.as_deref())
+                    .map(|undo_guid| (feed_page, expiring_items, weekly_items, undo_guid))
+            })
+    };
+
+    let (feed_page, expiring_items, weekly_items, undo_guid) = match result {
+        Ok(items) => items,
+        Err(err) => {
+            error!("{err}");
+            return Response::builder()
+                .status(StatusCode::INTERNAL_SERVER_ERROR)
+                .body(String::new())
+                .unwrap();
+        }
+    };
+
+    let mut writer = T::new(
+        &user.feed_name,
+        &user.feed_desc,
+        state.args.link.as_str(),
+        SystemTime::now(),
+    );
+    writer.set_csrf_token(&csrf_token.0);
+    let feed = build_feed(
+        writer,
+        feed_page,
+        expiring_items,
+        weekly_items,
+        state.args.expiring_window,
+        &filter,
+        undo_guid.as_deref(),
+    );
+
+    Response::builder()
+        .status(StatusCode::OK)
+        .header("Content-Type", T::CONTENT_TYPE)
+        .body(feed)
+        .unwrap()
+}
+
+/// Feed for readers, answered with 304 or from memory while the articles are unchanged
+async fn handle_get_cached_feed<T: FeedWriter>(
+    state: App,
+    user: User,
+    path: &str,
+    page: PageQuery,
+    filter: FeedFilter,
+    headers: HeaderMap,
+) -> Response<String> {
+    info!(
+        "get_cached_feed: {} {} {page:?} {filter:?}",
+        user.name,
+        T::CONTENT_TYPE
+    );
+
+    let page = Page::new(&page, state.args.feed_limit);
+
+    let mut db_lock = state.db_connection.lock().unwrap();
+
+    let revision = match expire_old_articles(&mut db_lock, &user)
+        .and_then(|_| fetch_last_change(&mut db_lock))
+    {
+        Ok(revision) => revision,
+        Err(err) => {
+            error!("{err}");
+            return Response::builder()
+                .status(StatusCode::INTERNAL_SERVER_ERROR)
+                .body(String::new())
+                .unwrap();
+        }
+    };
+
+    let validators = Validators::new(T::CONTENT_TYPE, revision);
+
+    let response = Response::builder()
+        .header("ETag", &validators.etag)
+        .header("Last-Modified", validators.last_modified_header())
+        .header("Cache-Control", FEED_CACHE_CONTROL);
+
+    if validators.is_fresh(&headers) {
+        return response
+            .status(StatusCode::NOT_MODIFIED)
+            .body(String::new())
+            .unwrap();
+    }
+
+    let cached_feed = state
+        .feed_cache
+        .lock()
+        .unwrap()
+        .get(user.id, path, &page, &filter, revision);
+
+    let feed = match cached_feed {
+        Some(feed) => feed,
+        None => {
+            let (feed_items, pagination) =
+                match fetch_feed_page(&mut db_lock, &user, &page, &filter) {
+                    Ok(items) => items,
+                    Err(err) => {
+                        error!("{err}");
+                        return Response::builder()
+                            .status(StatusCode::INTERNAL_SERVER_ERROR)
+                            .body(String::new())
+                            .unwrap();
+                    }
+                };
+            drop(db_lock);
+
+            let mut writer = T::new_at(
+                &user.feed_name,
+                &user.feed_desc,
+                state.args.link.as_str(),
+                path,
+                validators.last_modified,
+            );
+            writer.set_extend_key(&state.extend_key);
+            writer.set_author(&user.name);
+            writer.write_pagination(&pagination);
+            writer.write_feed_items(feed_items);
+            let feed = writer.finish();
+
+            state.feed_cache.lock().unwrap().insert(
+                user.id,
+                path,
+                page,
+                filter,
+                revision,
+                feed.clone(),
+            );
+
+            feed
+        }
+    };
+
+    response
+        .status(StatusCode::OK)
+        .header("Content-Type", T::CONTENT_TYPE)
+        .body(feed)
+        .unwrap()
+}
+
+async fn handle_get_stats_html(
+    State(state): State<App>,
+    Extension(user): Extension<User>,
+) -> Response<String> {
+    info!("get_stats_html: {}", user.name);
+
+    let stats = match get_stats(&state, &user) {
+        Ok(stats) => stats,
+        Err(err) => {
+            error!("{err}");
+            return Response::builder()
+                .status(StatusCode::INTERNAL_SERVER_ERROR)
+                .body(String::new())
+                .unwrap();
+        }
+    };
+
+    let title = format!("{}: stats", user.feed_name);
+    let mut writer = HtmlWriter::new(
+        &title,
+        &user.feed_desc,
+        state.args.link.as_str(),
+        SystemTime::now(),
+    );
+    writer.write_stats(&stats);
+    let page = writer.finish();
+
+    Response::builder()
+        .status(StatusCode::OK)
+        .header("Content-Type", HtmlWriter::CONTENT_TYPE)
+        .body(page)
+        .unwrap()
+}
+
+async fn handle_get_stats_json(
+    State(state): State<App>,
+    Extension(user): Extension<User>,
+) -> Result<Json<Stats>, StatusCode> {
+    info!("get_stats_json: {}", user.name);
+
+    get_stats(&state, &user).map(Json).map_err(|err| {
+        error!("{err}");
+        StatusCode::INTERNAL_SERVER_ERROR
+    })
+}
+
+fn get_stats(state: &App, user: &User) -> anyhow::Result<Stats> {
+    let mut db_lock = state.db_connection.lock().unwrap();
+
+    expire_old_articles(&mut db_lock, user)?;
+    fetch_stats(&mut db_lock, user)
+}
+
+async fn handle_search_html(
+    State(state): State<App>,
+    Extension(user): Extension<User>,
+    Query(query): Query<SearchParams>,
+) -> Response<String> {
+    handle_search::<HtmlWriter>(state, user, query).await
+}
+
+async fn handle_search_xml(
+    State(state): State<App>,
+    Extension(user): Extension<User>,
+    Query(query): Query<SearchParams>,
+) -> Response<String> {
+    handle_search::<RssWriter>(state, user, query).await
+}
+
+async fn handle_search<T: FeedWriter>(
+    state: App,
+    user: User,
+    params: SearchParams,
+) -> Response<String> {
+    info!("search: {} {params:?}", user.name);
+
+    let result = {
+        let mut db_lock = state.db_connection.lock().unwrap();
+
+        expire_old_articles(&mut db_lock, &user).and_then(|_| fetch_feed(&mut db_lock, &user))
+    };
+
+    let feed_items = match result {
+        Ok(items) => items,
+        Err(err) => {
+            error!("{err}");
+            return Response::builder()
+                .status(StatusCode::INTERNAL_SERVER_ERROR)
+                .body(String::new())
+                .unwrap();
+        }
+    };
+
+    let query = SearchQuery::parse(&params.q);
+    let found_items = query.rank(feed_items);
+
+    let title = format!("{}: {}", user.feed_name, query.as_str());
+    let mut writer = T::new(
+        &title,
+        &user.feed_desc,
+        state.args.link.as_str(),
+        SystemTime::now(),
+    );
+    writer.set_extend_key(&state.extend_key);
+    writer.set_author(&user.name);
+    writer.write_search_items(&query, found_items);
+    let feed = writer.finish();
+
+    Response::builder()
+        .status(StatusCode::OK)
+        .header("Content-Type", T::CONTENT_TYPE)
+        .body(feed)
+        .unwrap()
+}
+
+#[derive(Debug, Deserialize)]
+struct DeleteForm {
+    guid: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct RestoreForm {
+    guid: String,
+}
+
+/// Fields left out stay as they are. Tags are comma separated and replace the current ones
+#[derive(Debug, Deserialize)]
+struct EditForm {
+    guid: String,
+    title: Option<String>,
+    link: Option<String>,
+    tags: Option<String>,
+    pinned: Option<bool>,
+}
+
+/// Change made to many articles at once
+#[derive(Debug, Deserialize)]
+#[serde(tag = "action", rename_all = "lowercase")]
+enum BulkAction {
+    Delete,
+    Read,
+    Tag { tags: Vec<String> },
+}
+
+#[derive(Debug, Deserialize)]
+struct RefetchForm {
+    guid: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct EditNoteForm {
+    id: i64,
+    text: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct NoteForm {
+    guid: String,
+    #[serde(default)]
+    kind: NoteKind,
+    text: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct DeleteNoteForm {
+    id: i64,
+}
+
+#[derive(Debug, Deserialize)]
+struct FeedQuery {
+    /// guid of a just deleted article
+    undo: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SearchParams {
+    #[serde(default)]
+    q: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ReadQuery {
+    guid: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ExtendQuery {
+    guid: String,
+}
+
+#[derive(Deserialize)]
+struct ExtendLinkQuery {
+    guid: String,
+    token: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct SaveForm {
+    url: String,
+    /// Saves into the shared collection instead of the user's own list
+    collection: Option<String>,
+    /// Title the browser saw, kept instead of the fetched one
+    title: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AddQuery {
+    redirect: Option<AddRedirect>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum AddRedirect {
+    Back,
+}
+
+#[derive(Debug)]
+struct Article {
+    url: Url,
+    title: String,
+    kind: DocumentKind,
+}
+
+impl Article {
+    /// Pages behind a login only show the browser their real title
+    fn titled(self, title: Option<&str>) -> Self {
+        match title.map(str::trim).filter(|title| !title.is_empty()) {
+            Some(title) => Self {
+                title: title.to_string(),
+                ..self
+            },
+            None => self,
+        }
+    }
+}
+
+/// What the saved link points at
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum DocumentKind {
+    Html,
+    Pdf,
+    Other,
+}
+
+impl DocumentKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            DocumentKind::Html => "html",
+            DocumentKind::Pdf => "pdf",
+            DocumentKind::Other => "other",
+        }
+    }
+}
+
+impl FromStr for DocumentKind {
+    type Err = anyhow::Error;
+
+    fn from_str(kind: &str) -> Result<Self, Self::Err> {
+        match kind {
+            "html" => Ok(DocumentKind::Html),
+            "pdf" => Ok(DocumentKind::Pdf),
+            "other" => Ok(DocumentKind::Other),
+            _ => bail!("unknown document kind {kind}"),
+        }
+    }
+}
+
+struct FeedItem {
+    title: String,
+    link: String,
+    pub_date: String,
+    expire_date: String,
+    pub_time: SystemTime,
+    guid: String,
+    kind: DocumentKind,
+    tags: Vec<String>,
+    notes: Vec<Note>,
+}
+
+/// Free-form note or a quoted highlight attached to an article
+struct Note {
+    id: i64,
+    kind: NoteKind,
+    text: String,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum NoteKind {
+    #[default]
+    Note,
+    Highlight,
+}
+
+impl NoteKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            NoteKind::Note => "note",
+            NoteKind::Highlight => "highlight",
+        }
+    }
+}
+
+impl FromStr for NoteKind {
+    type Err = anyhow::Error;
+
+    fn from_str(kind: &str) -> Result<Self, Self::Err> {
+        match kind {
+            "note" => Ok(NoteKind::Note),
+            "highlight" => Ok(NoteKind::Highlight),
+            _ => bail!("unknown note kind {kind}"),
+        }
+    }
+}
+
+/// Notes as paragraphs and highlights as quotes, the same in pages and feeds
+fn push_notes(buffer: &mut Markup, notes: &[Note]) {
+    for note in notes {
+        match note.kind {
+            NoteKind::Note => buffer.push_raw("<p>"),
+            NoteKind::Highlight => buffer.push_raw("<blockquote>"),
+        }
+        buffer.push_text(&note.text);
+        match note.kind {
+            NoteKind::Note => buffer.push_raw("</p>"),
+            NoteKind::Highlight => buffer.push_raw("</blockquote>"),
+        }
+    }
+}
+
+struct TrashItem {
+    title: String,
+    link: String,
+    deleted_date: String,
+    purge_date: String,
+    guid: String,
+}
+
+/// Activity of a single week, starting on Monday
+struct WeeklyItem {
+    week: String,
+    saved: i64,
+    deleted: i64,
+    expired: i64,
+    read: i64,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Activity {
+    Saved,
+    Deleted,
+    Expired,
+    ExpiredUnread,
+    Read,
+    FetchFailed,
+}
+
+impl Activity {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Activity::Saved => "saved",
+            Activity::Deleted => "deleted",
+            Activity::Expired => "expired",
+            Activity::ExpiredUnread => "expired_unread",
+            Activity::Read => "read",
+            Activity::FetchFailed => "fetch_failed",
+        }
+    }
+}
+
+/// The page behind a link couldn't be fetched, as opposed to failing to store it
+#[derive(Debug)]
+struct FetchError(anyhow::Error);
+
+impl fmt::Display for FetchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl std::error::Error for FetchError {}
+
+/// Fetches and stores an article of the user, returning its guid. A title the
+/// client already knows is kept instead of the fetched one. Failing to fetch is
+/// a `FetchError`
+async fn add_article(
+    url: &str,
+    title: Option<&str>,
+    user: &User,
+    db_connection: DbConnection,
+) -> anyhow::Result<String> {
+    let article = fetch_article(url)
+        .await
+        .map(|article| article.titled(title));
+
+    let mut db_lock = db_connection.lock().unwrap();
+
+    let article = match article {
+        Ok(article) => article,
+        Err(err) => {
+            let tx = db_lock.transaction()?;
+            record_activity(&tx, user, Activity::FetchFailed, 1)?;
+            tx.commit()?;
+            return Err(FetchError(err).into());
+        }
+    };
+
+    store_article(&mut db_lock, user, article)
+}
+
+/// Updates the title from the page without resetting the article's save date
+async fn refetch_article(
+    guid: &str,
+    user: &User,
+    db_connection: DbConnection,
+) -> anyhow::Result<()> {
+    let link = {
+        let db_lock = db_connection.lock().unwrap();
+        db_lock.query_row(
+            "SELECT link FROM articles WHERE guid = ? AND user_id = ?",
+            params![guid, user.id],
+            |row| row.get::<_, String>(0),
+        )?
+    };
+
+    let article = fetch_article(&link).await?;
+
+    let mut db_lock = db_connection.lock().unwrap();
+    let tx = db_lock.transaction()?;
+    tx.execute(
+        "UPDATE articles SET title = ?, kind = ? WHERE guid = ? AND user_id = ?",
+        params![article.title, article.kind.as_str(), guid, user.id],
+    )?;
+    record_change(&tx)?;
+    tx.commit()?;
+
+    Ok(())
+}
+
+async fn fetch_article(url: &str) -> anyhow::Result<Article> {
+    let fetch_and_extract = async {
+        let document = request_document(url).await?;
+        extract_article(document).await
+    };
+
+    timeout(Duration::from_secs(5), fetch_and_extract).await?
+}
+
+async fn extract_article(document: Document<PlainOrTls>) -> anyhow::Result<Article> {
+    let (url, title, kind) = match document {
+        Document::Unsupported(url) => {
+            let title = format!("[???] {url}");
+            (url, title, DocumentKind::Other)
+        }
+        Document::Pdf(url) => {
+            let title = url
+                .path_segments()
+                .and_then(|mut segments| segments.next_back())
+                .map(ToString::to_string)
+                .unwrap_or_else(|| url.to_string());
+            let title = format!("[PDF] {title}");
+            (url, title, DocumentKind::Pdf)
+        }
+        Document::Html(url, mut http_body_reader) => {
+            let title = http_body_reader.extract_title().await?;
+            let title = title.unwrap_or_else(|| "[NO TITLE]".to_string());
+            (url, title, DocumentKind::Html)
+        }
+    };
+
+    Ok(Article { url, title, kind })
+}
+
+fn setup_tables(db_connection: &mut Connection) -> anyhow::Result<()> {
+    db_connection.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS articles (
+            timestamp TIMESTAMP WITH TIME ZONE NOT NULL,
+            title TEXT NOT NULL,
+            link TEXT NOT NULL,
+            guid TEXT NOT NULL);
+        ALTER TABLE articles ADD COLUMN IF NOT EXISTS extended_at TIMESTAMP WITH TIME ZONE;
+        ALTER TABLE articles ADD COLUMN IF NOT EXISTS deleted_at TIMESTAMP WITH TIME ZONE;
+        ALTER TABLE articles ADD COLUMN IF NOT EXISTS read_at TIMESTAMP WITH TIME ZONE;
+        ALTER TABLE articles ADD COLUMN IF NOT EXISTS kind TEXT;
+        ALTER TABLE articles ADD COLUMN IF NOT EXISTS pinned_at TIMESTAMP WITH TIME ZONE;
+        ALTER TABLE articles ADD COLUMN IF NOT EXISTS user_id INT64 DEFAULT 0;
+        CREATE SEQUENCE IF NOT EXISTS users_id START 1;
+        CREATE TABLE IF NOT EXISTS users (
+            id INT64 NOT NULL PRIMARY KEY DEFAULT nextval('users_id'),
+            name TEXT NOT NULL UNIQUE,
+            ttl INT64,
+            feed_name TEXT,
+            feed_desc TEXT,
+            created_at TIMESTAMP WITH TIME ZONE NOT NULL);
+        INSERT INTO users (id, name, created_at)
+        SELECT 0, 'default', current_timestamp
+        WHERE NOT EXISTS (SELECT 1 FROM users WHERE id = 0);
+        CREATE SEQUENCE IF NOT EXISTS notes_id;
+        CREATE TABLE IF NOT EXISTS notes (
+            id INT64 NOT NULL PRIMARY KEY DEFAULT nextval('notes_id'),
+            timestamp TIMESTAMP WITH TIME ZONE NOT NULL,
+            guid TEXT NOT NULL,
+            kind TEXT NOT NULL,
+            text TEXT NOT NULL);
+        CREATE TABLE IF NOT EXISTS tags (
+            guid TEXT NOT NULL,
+            tag TEXT NOT NULL,
+            PRIMARY KEY (guid, tag));
+        CREATE TABLE IF NOT EXISTS activity (
+            user_id INT64 NOT NULL DEFAULT 0,
+            day DATE NOT NULL,
+            kind TEXT NOT NULL,
+            count INT64 NOT NULL,
+            PRIMARY KEY (user_id, day, kind));
+        CREATE TABLE IF NOT EXISTS changes (
+            timestamp TIMESTAMP WITH TIME ZONE NOT NULL);
+        CREATE TABLE IF NOT EXISTS tokens (
+            name TEXT NOT NULL PRIMARY KEY,
+            hash TEXT NOT NULL,
+            created_at TIMESTAMP WITH TIME ZONE NOT NULL,
+            used_at TIMESTAMP WITH TIME ZONE);
+        CREATE TABLE IF NOT EXISTS keys (
+            name TEXT NOT NULL PRIMARY KEY,
+            key TEXT NOT NULL);
+        CREATE TABLE IF NOT EXISTS feed_tokens (
+            name TEXT NOT NULL PRIMARY KEY,
+            hash TEXT NOT NULL,
+            scope TEXT NOT NULL,
+            tag TEXT,
+            created_at TIMESTAMP WITH TIME ZONE NOT NULL,
+            used_at TIMESTAMP WITH TIME ZONE);
+        CREATE SEQUENCE IF NOT EXISTS collections_id;
+        CREATE TABLE IF NOT EXISTS collections (
+            id INT64 NOT NULL PRIMARY KEY DEFAULT nextval('collections_id'),
+            name TEXT NOT NULL UNIQUE,
+            title TEXT,
+            description TEXT,
+            link TEXT,
+            ttl INT64,
+            public BOOLEAN NOT NULL DEFAULT false,
+            created_at TIMESTAMP WITH TIME ZONE NOT NULL);
+        CREATE TABLE IF NOT EXISTS collection_members (
+            collection_id INT64 NOT NULL,
+            user_id INT64 NOT NULL,
+            PRIMARY KEY (collection_id, user_id));
+        CREATE TABLE IF NOT EXISTS collection_articles (
+            collection_id INT64 NOT NULL,
+            user_id INT64 NOT NULL,
+            timestamp TIMESTAMP WITH TIME ZONE NOT NULL,
+            title TEXT NOT NULL,
+            link TEXT NOT NULL,
+            guid TEXT NOT NULL,
+            kind TEXT NOT NULL,
+            PRIMARY KEY (collection_id, guid));
+        ALTER TABLE tokens ADD COLUMN IF NOT EXISTS user_id INT64 DEFAULT 0;
+        ALTER TABLE feed_tokens ADD COLUMN IF NOT EXISTS user_id INT64 DEFAULT 0;",
+    )?;
+
+    // activity counted before there were users belongs to the default user
+    let is_shared_activity = db_connection.query_row(
+        "SELECT NOT EXISTS (
+            SELECT 1 FROM information_schema.columns
+            WHERE table_name = 'activity' AND column_name = 'user_id')",
+        [],
+        |row| row.get::<_, bool>(0),
+    )?;
+    if is_shared_activity {
+        db_connection.execute_batch(
+            "
+            CREATE TABLE activity_per_user (
+                user_id INT64 NOT NULL DEFAULT 0,
+                day DATE NOT NULL,
+                kind TEXT NOT NULL,
+                count INT64 NOT NULL,
+                PRIMARY KEY (user_id, day, kind));
+            INSERT INTO activity_per_user (user_id, day, kind, count)
+            SELECT 0, day, kind, count FROM activity;
+            DROP TABLE activity;
+            ALTER TABLE activity_per_user RENAME TO activity;",
+        )?;
+    }
+
+    db_connection.execute_batch(
+        "
+        INSERT INTO changes (timestamp)
+        SELECT current_timestamp
+        WHERE NOT EXISTS (SELECT 1 FROM changes);",
+    )?;
+
+    // articles saved before the kind was stored are told apart by their placeholder titles
+    db_connection.execute_batch(
+        "
+        UPDATE articles SET kind = CASE
+            WHEN starts_with(title, '[PDF] ') THEN 'pdf'
+            WHEN starts_with(title, '[???] ') THEN 'other'
+            ELSE 'html'
+        END
+        WHERE kind IS NULL;",
+    )?;
+
+    // history before the activity table only knows when live articles were saved. The
+    // weekly stats of earlier versions are left alone in stats_per_week_of_year, they
+    // counted the whole library in weeks without a year
+    db_connection.execute_batch(
+        "
+        INSERT INTO activity (user_id, day, kind, count)
+        SELECT user_id, CAST(make_timestamp(epoch_us(timestamp)) AS DATE), 'saved', count(*)
+        FROM articles
+        WHERE NOT EXISTS (SELECT 1 FROM activity)
+        GROUP BY user_id, CAST(make_timestamp(epoch_us(timestamp)) AS DATE);",
+    )?;
+
+    Ok(())
+}
+
+fn store_article(
+    db_connection: &mut Connection,
+    user: &User,
+    article: Article,
+) -> anyhow::Result<String> {
+    let tx = db_connection.transaction()?;
+
+    // an article edited to this link keeps its guid, and with it its notes and tags
+    let guid = tx
+        .query_row(
+            "SELECT guid FROM articles WHERE user_id = ? AND link = ?
+            ORDER BY timestamp DESC
+            LIMIT 1",
+            params![user.id, article.url.as_str()],
+            |row| row.get::<_, String>(0),
+        )
+        .optional()?
+        .unwrap_or_else(|| user.guid(article.url.as_str()));
+
+    // saving again restarts the time to live, but the article stays pinned or read
+    let updated = tx.execute(
+        "UPDATE articles SET
+        title = ?, link = ?, kind = ?, timestamp = current_timestamp,
+        extended_at = NULL, deleted_at = NULL
+        WHERE user_id = ? AND guid = ?",
+        params![
+            article.title,
+            article.url.as_str(),
+            article.kind.as_str(),
+            user.id,
+            &guid
+        ],
+    )?;
+    if updated == 0 {
+        tx.execute(
+            "INSERT INTO articles 
+            (user_id, title, link, guid, kind, timestamp)
+            VALUES
+            (?, ?, ?, ?, ?, current_timestamp)",
+            params![
+                user.id,
+                article.title,
+                article.url.as_str(),
+                &guid,
+                article.kind.as_str()
+            ],
+        )?;
+    }
+    record_activity(&tx, user, Activity::Saved, 1)?;
+    record_change(&tx)?;
+    tx.commit()?;
+
+    Ok(guid)
+}
+
+/// An edit the article can't take, as opposed to failing to store it
+#[derive(Debug)]
+struct InvalidEdit(String);
+
+impl fmt::Display for InvalidEdit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl std::error::Error for InvalidEdit {}
+
+/// Invalid values are an `InvalidEdit`
+fn edit_article(
+    db_connection: &mut Connection,
+    user: &User,
+    edit: &EditForm,
+) -> anyhow::Result<()> {
+    let title = edit
+        .title
+        .as_deref()
+        .map(str::trim)
+        .filter(|title| !title.is_empty());
+    let link = match edit.link.as_deref().map(str::trim) {
+        Some(link) if !link.is_empty() => Some(parse_http_url(link).map_err(InvalidEdit)?),
+        _ => None,
+    };
+
+    let tx = db_connection.transaction()?;
+
+    // saving a link finds its article by the link, so it can't be shared
+    if let Some(link) = &link {
+        let is_taken = tx.query_row(
+            "SELECT EXISTS (
+                SELECT 1 FROM articles WHERE user_id = ? AND link = ? AND guid != ?)",
+            params![user.id, link, &edit.guid],
+            |row| row.get::<_, bool>(0),
+        )?;
+        if is_taken {
+            return Err(InvalidEdit(String::from("link of another article")).into());
+        }
+    }
+
+    let updated = tx.execute(
+        "UPDATE articles SET
+        title = coalesce(?, title),
+        link = coalesce(?, link),
+        pinned_at = CASE CAST(? AS BOOLEAN)
+            WHEN true THEN coalesce(pinned_at, current_timestamp)
+            WHEN false THEN NULL
+            ELSE pinned_at
+        END
+        WHERE guid = ? AND user_id = ?",
+        params![title, link, edit.pinned, &edit.guid, user.id],
+    )?;
+    if updated == 0 {
+        bail!("no article {}", edit.guid);
+    }
+
+    if let Some(tags) = &edit.tags {
+        tx.execute("DELETE FROM tags WHERE guid = ?", [&edit.guid])?;
+        for tag in parse_tags(tags) {
+            tx.execute(
+                "INSERT INTO tags (guid, tag) VALUES (?, ?)",
+                params![&edit.guid, tag],
+            )?;
+        }
+    }
+
+    record_change(&tx)?;
+    tx.commit()?;
+
+    Ok(())
+}
+
+/// Lowercase, deduplicated tags from a comma separated list
+fn parse_tags(tags: &str) -> Vec<String> {
+    let mut tags = tags
+        .split(',')
+        .map(|tag| tag.trim().to_lowercase())
+        .filter(|tag| !tag.is_empty())
+        .collect::<Vec<_>>();
+    tags.sort();
+    tags.dedup();
+    tags
+}
+
+/// Moves an article to trash
+fn delete_article(db_connection: &mut Connection, user: &User, guid: &str) -> anyhow::Result<()> {
+    let tx = db_connection.transaction()?;
+    if trash_article(&tx, user, guid)? > 0 {
+        record_change(&tx)?;
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+/// Returns 1 if a live article was moved to trash
+fn trash_article(tx: &Transaction, user: &User, guid: &str) -> anyhow::Result<usize> {
+    let deleted = tx.execute(
+        "UPDATE articles SET deleted_at = current_timestamp
+        WHERE guid = ? AND user_id = ? AND deleted_at IS NULL",
+        params![guid, user.id],
+    )?;
+    record_activity(tx, user, Activity::Deleted, deleted)?;
+    Ok(deleted)
+}
+
+/// Takes an article out of trash. An expired article gets a fresh time to live
+fn restore_article(db_connection: &mut Connection, user: &User, guid: &str) -> anyhow::Result<()> {
+    let tx = db_connection.transaction()?;
+    let restored = tx.execute(
+        "UPDATE articles SET
+        deleted_at = NULL,
+        extended_at = CASE
+            WHEN (current_timestamp AT TIME ZONE 'UTC' - coalesce(extended_at, timestamp) AT TIME ZONE 'UTC') > INTERVAL (?) DAY
+            THEN current_timestamp
+            ELSE extended_at
+        END
+        WHERE guid = ? AND user_id = ?",
+        params![user.ttl, guid, user.id],
+    )?;
+    if restored > 0 {
+        record_change(&tx)?;
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+/// The guid if it is of an article in the user's trash, for the undo notice
+fn fetch_trashed_guid(
+    db_connection: &mut Connection,
+    user: &User,
+    guid: Option<&str>,
+) -> anyhow::Result<Option<String>> {
+    let Some(guid) = guid else {
+        return Ok(None);
+    };
+
+    let is_trashed = db_connection.query_row(
+        "SELECT EXISTS (
+            SELECT 1 FROM articles
+            WHERE guid = ? AND user_id = ? AND deleted_at IS NOT NULL)",
+        params![guid, user.id],
+        |row| row.get::<_, bool>(0),
+    )?;
+
+    Ok(is_trashed.then(|| guid.to_string()))
+}
+
+/// Records the first read. Returns false if the user has no such live article
+fn read_article(db_connection: &mut Connection, user: &User, guid: &str) -> anyhow::Result<bool> {
+    let tx = db_connection.transaction()?;
+    let exists = tx.query_row(
+        "SELECT EXISTS (SELECT 1 FROM articles WHERE guid = ? AND user_id = ? AND deleted_at IS NULL)",
+        params![guid, user.id],
+        |row| row.get::<_, bool>(0),
+    )?;
+    if exists && mark_read(&tx, user, guid)? > 0 {
+        record_change(&tx)?;
+    }
+    tx.commit()?;
+    Ok(exists)
+}
+
+/// Returns 1 if this was the first read of the article. Articles in trash are left as they are
+fn mark_read(tx: &Transaction, user: &User, guid: &str) -> anyhow::Result<usize> {
+    let read = tx.execute(
+        "UPDATE articles SET read_at = current_timestamp
+        WHERE guid = ? AND user_id = ? AND read_at IS NULL AND deleted_at IS NULL",
+        params![guid, user.id],
+    )?;
+    record_activity(tx, user, Activity::Read, read)?;
+    Ok(read)
+}
+
+/// Adds tags to an article, keeping the current ones. Returns the number of new tags
+fn tag_article(
+    tx: &Transaction,
+    user: &User,
+    guid: &str,
+    tags: &[String],
+) -> anyhow::Result<usize> {
+    let mut tagged = 0;
+    for tag in parse_tags(&tags.join(",")) {
+        tagged += tx.execute(
+            "INSERT INTO tags (guid, tag)
+            SELECT guid, ? FROM articles WHERE guid = ? AND user_id = ?
+            ON CONFLICT DO NOTHING",
+            params![tag, guid, user.id],
+        )?;
+    }
+    Ok(tagged)
+}
+
+/// Applies the action to all articles in one transaction, returning how many changed
+fn bulk_update(
+    db_connection: &mut Connection,
+    user: &User,
+    guids: &[String],
+    action: &BulkAction,
+) -> anyhow::Result<usize> {
+    let tx = db_connection.transaction()?;
+
+    let mut changed = 0;
+    for guid in guids {
+        changed += match action {
+            BulkAction::Delete => trash_article(&tx, user, guid)?,
+            BulkAction::Read => mark_read(&tx, user, guid)?,
+            BulkAction::Tag { tags } => usize::from(tag_article(&tx, user, guid, tags)? > 0),
+        };
+    }
+
+    if changed > 0 {
+        record_change(&tx)?;
+    }
+    tx.commit()?;
+    Ok(changed)
+}
+
+/// Restarts the time to live of an article without touching its save date
+fn extend_article(db_connection: &mut Connection, user: &User, guid: &str) -> anyhow::Result<()> {
+    let tx = db_connection.transaction()?;
+    let extended = tx.execute(
+        "UPDATE articles SET extended_at = current_timestamp
+        WHERE guid = ? AND user_id = ? AND deleted_at IS NULL",
+        params![guid, user.id],
+    )?;
+    if extended > 0 {
+        record_change(&tx)?;
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+/// Moves the user's articles whose time to live has ended to trash
+fn expire_old_articles(db_connection: &mut Connection, user: &User) -> anyhow::Result<()> {
+    let tx = db_connection.transaction()?;
+    let expired_unread = tx.query_row(
+        "SELECT count(*) FROM articles
+        WHERE user_id = ? AND deleted_at IS NULL AND read_at IS NULL
+        AND (current_timestamp AT TIME ZONE 'UTC' - coalesce(extended_at, timestamp) AT TIME ZONE 'UTC') > INTERVAL (?) DAY",
+        params![user.id, user.ttl],
+        |row| row.get::<_, usize>(0),
+    )?;
+    let expired = tx.execute(
+        "UPDATE articles SET deleted_at = current_timestamp
+        WHERE user_id = ? AND deleted_at IS NULL
+        AND (current_timestamp AT TIME ZONE 'UTC' - coalesce(extended_at, timestamp) AT TIME ZONE 'UTC') > INTERVAL (?) DAY",
+        params![user.id, user.ttl],
+    )?;
+    record_activity(&tx, user, Activity::Expired, expired)?;
+    record_activity(&tx, user, Activity::ExpiredUnread, expired_unread)?;
+    if expired > 0 {
+        record_change(&tx)?;
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+/// Finally deletes articles which stayed in trash longer than the grace period
+fn purge_trash(db_connection: &mut Connection, args: &Args) -> anyhow::Result<()> {
+    let tx = db_connection.transaction()?;
+    let purged = tx.execute(
+        "DELETE FROM articles
+        WHERE deleted_at IS NOT NULL
+        AND (current_timestamp AT TIME ZONE 'UTC' - deleted_at AT TIME ZONE 'UTC') > INTERVAL (?) DAY",
+        [args.trash_grace],
+    )?;
+    tx.execute(
+        "DELETE FROM notes WHERE guid NOT IN (SELECT guid FROM articles)",
+        [],
+    )?;
+    tx.execute(
+        "DELETE FROM tags WHERE guid NOT IN (SELECT guid FROM articles)",
+        [],
+    )?;
+    if purged > 0 {
+        record_change(&tx)?;
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+fn add_note(db_connection: &mut Connection, user: &User, note: &NoteForm) -> anyhow::Result<()> {
+    let text = note.text.trim();
+    if text.is_empty() {
+        bail!("empty note");
+    }
+
+    let tx = db_connection.transaction()?;
+    let inserted = tx.execute(
+        "INSERT INTO notes
+        (guid, kind, text, timestamp)
+        SELECT guid, ?, ?, current_timestamp
+        FROM articles
+        WHERE guid = ? AND user_id = ?",
+        params![note.kind.as_str(), text, &note.guid, user.id],
+    )?;
+    if inserted == 0 {
+        bail!("no article {}", note.guid);
+    }
+    record_change(&tx)?;
+    tx.commit()?;
+
+    Ok(())
+}
+
+fn edit_note(
+    db_connection: &mut Connection,
+    user: &User,
+    id: i64,
+    text: &str,
+) -> anyhow::Result<()> {
+    let text = text.trim();
+    if text.is_empty() {
+        return delete_note(db_connection, user, id);
+    }
+
+    let tx = db_connection.transaction()?;
+    let updated = tx.execute(
+        "UPDATE notes SET text = ?
+        WHERE id = ? AND guid IN (SELECT guid FROM articles WHERE user_id = ?)",
+        params![text, id, user.id],
+    )?;
+    if updated > 0 {
+        record_change(&tx)?;
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+fn delete_note(db_connection: &mut Connection, user: &User, id: i64) -> anyhow::Result<()> {
+    let tx = db_connection.transaction()?;
+    let deleted = tx.execute(
+        "DELETE FROM notes
+        WHERE id = ? AND guid IN (SELECT guid FROM articles WHERE user_id = ?)",
+        params![id, user.id],
+    )?;
+    if deleted > 0 {
+        record_change(&tx)?;
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+/// Counts the user's activity per UTC day
+fn record_activity(
+    tx: &Transaction,
+    user: &User,
+    activity: Activity,
+    count: usize,
+) -> anyhow::Result<()> {
+    if count == 0 {
+        return Ok(());
+    }
+
+    tx.execute(
+        "INSERT INTO activity (user_id, day, kind, count)
+        VALUES (?, CAST(make_timestamp(epoch_us(current_timestamp)) AS DATE), ?, ?)
+        ON CONFLICT (user_id, day, kind) DO UPDATE SET count = count + excluded.count",
+        params![user.id, activity.as_str(), count as i64],
+    )?;
+    Ok(())
+}
+
+/// Marks the articles as changed, invalidating cached feeds
+fn record_change(tx: &Transaction) -> anyhow::Result<()> {
+    tx.execute("UPDATE changes SET timestamp = current_timestamp", [])?;
+    Ok(())
+}
+
+/// Time of the last change to the articles in microseconds
+fn fetch_last_change(db_connection: &mut Connection) -> anyhow::Result<i64> {
+    let revision = db_connection.query_row(
+        "SELECT epoch_us(CAST(timestamp AS TIMESTAMP)) FROM changes",
+        [],
+        |row| row.get(0),
+    )?;
+    Ok(revision)
+}
+
+fn fetch_feed(db_connection: &mut Connection, user: &User) -> anyhow::Result<Vec<FeedItem>> {
+    let mut select_stmt = db_connection.prepare(&format!(
+        "SELECT {FEED_ITEM_COLUMNS}
+        FROM articles
+        WHERE user_id = ? AND deleted_at IS NULL
+        ORDER BY timestamp DESC"
+    ))?;
+
+    let rows = select_stmt.query(params![user.ttl, user.id])?;
+    let mut items = read_feed_items(rows)?;
+    drop(select_stmt);
+
+    attach_tags_and_notes(db_connection, user, &mut items)?;
+
+    Ok(items)
+}
+
+/// A live article of the user with its tags and notes
+fn fetch_feed_item(
+    db_connection: &mut Connection,
+    user: &User,
+    guid: &str,
+) -> anyhow::Result<Option<FeedItem>> {
+    let mut select_stmt = db_connection.prepare(&format!(
+        "SELECT {FEED_ITEM_COLUMNS}
+        FROM articles
+        WHERE user_id = ? AND guid = ? AND deleted_at IS NULL"
+    ))?;
+
+    let rows = select_stmt.query(params![user.ttl, user.id, guid])?;
+    let Some(mut item) = read_feed_items(rows)?.pop() else {
+        return Ok(None);
+    };
+    drop(select_stmt);
+
+    let mut select_stmt =
+        db_connection.prepare("SELECT tag FROM tags WHERE guid = ? ORDER BY tag ASC")?;
+    item.tags = select_stmt
+        .query_map([guid], |row| row.get::<_, String>(0))?
+        .collect::<Result<_, _>>()?;
+
+    let mut select_stmt = db_connection.prepare(
+        "SELECT 
+        id, kind, text
+        FROM notes
+        WHERE guid = ?
+        ORDER BY timestamp ASC, id ASC",
+    )?;
+    let mut rows = select_stmt.query([guid])?;
+    while let Some(row) = rows.next()? {
+        let kind: String = row.get(1)?;
+        item.notes.push(Note {
+            id: row.get(0)?,
+            kind: kind.parse()?,
+            text: row.get(2)?,
+        });
+    }
+
+    Ok(Some(item))
+}
+
+/// Live articles matching the filter and in the page,
+/// and links to the pages around it
+fn fetch_feed_page(
+    db_connection: &mut Connection,
+    user: &User,
+    page: &Page,
+    filter: &FeedFilter,
+) -> anyhow::Result<(Vec<FeedItem>, Pagination)> {
+    let sort = filter.sort();
+
+    // the cursor is a save time and guid, it only follows the newest first order
+    let page = match sort {
+        SortOrder::Saved => *page,
+        _ => Page {
+            before: None,
+            ..*page
+        },
+    };
+
+    let domain = filter.domain();
+    let tag = filter.tag();
+    let kind = filter.kind.map(|kind| kind.as_str());
+    let read = filter.read.map(|read| read == ReadState::Read);
+    let pinned = filter.pinned;
+    let before_time = page.before.map(|cursor| cursor.time);
+    let before_guid = page.before.map(|cursor| cursor.guid.to_string());
+
+    let matching = "user_id = ? AND deleted_at IS NULL
+        AND (CAST(? AS INT64) IS NULL
+            OR epoch_us(CAST(timestamp AS TIMESTAMP)) < ?
+            OR (epoch_us(CAST(timestamp AS TIMESTAMP)) = ? AND guid < ?))
+        AND (CAST(? AS TEXT) IS NULL
+            OR regexp_extract(link, '^[a-z]+://(?:www\\.)?([^/:?#]+)', 1) = lower(?)
+            OR ends_with(regexp_extract(link, '^[a-z]+://([^/:?#]+)', 1), '.' || lower(?)))
+        AND (CAST(? AS TEXT) IS NULL OR guid IN (SELECT guid FROM tags WHERE tag = ?))
+        AND (CAST(? AS TEXT) IS NULL OR kind = ?)
+        AND (CAST(? AS BOOLEAN) IS NULL OR (read_at IS NOT NULL) = ?)
+        AND (NOT ? OR pinned_at IS NOT NULL)";
+
+    let mut select_stmt = db_connection.prepare(&format!(
+        "SELECT {FEED_ITEM_COLUMNS}
+        FROM articles
+        WHERE {matching}
+        ORDER BY {}
+        LIMIT ? OFFSET ?",
+        sort.order_by()
+    ))?;
+
+    let rows = select_stmt.query(params![
+        user.ttl,
+        user.id,
+        before_time,
+        before_time,
+        before_time,
+        before_guid,
+        domain,
+        domain,
+        domain,
+        tag,
+        tag,
+        kind,
+        kind,
+        read,
+        read,
+        pinned,
+        page.limit,
+        page.offset()
+    ])?;
+    let mut items = read_feed_items(rows)?;
+    drop(select_stmt);
+
+    let count = db_connection.query_row(
+        &format!("SELECT count(*) FROM articles WHERE {matching}"),
+        params![
+            user.id,
+            before_time,
+            before_time,
+            before_time,
+            before_guid,
+            domain,
+            domain,
+            domain,
+            tag,
+            tag,
+            kind,
+            kind,
+            read,
+            read,
+            pinned
+        ],
+        |row| row.get::<_, u64>(0),
+    )?;
+
+    let oldest = items.last().and_then(|item| {
+        Some(Cursor {
+            time: epoch_micros(item.pub_time),
+            guid: Uuid::parse_str(&item.guid).ok()?,
+        })
+    });
+    let pagination = Pagination::new(&page, count, oldest, &filter.to_query());
+
+    attach_tags_and_notes(db_connection, user, &mut items)?;
+
+    Ok((items, pagination))
+}
+
+fn attach_tags_and_notes(
+    db_connection: &mut Connection,
+    user: &User,
+    items: &mut [FeedItem],
+) -> anyhow::Result<()> {
+    let mut tags = fetch_tags(db_connection, user)?;
+    let mut notes = fetch_notes(db_connection, user)?;
+    for item in items {
+        item.tags = tags.remove(&item.guid).unwrap_or_default();
+        item.notes = notes.remove(&item.guid).unwrap_or_default();
+    }
+
+    Ok(())
+}
+
+fn epoch_micros(time: SystemTime) -> i64 {
+    time.duration_since(SystemTime::UNIX_EPOCH)
+        .map(|duration| duration.as_micros() as i64)
+        .unwrap_or(0)
+}
+
+/// Tags of the user's articles grouped by guid, sorted alphabetically
+fn fetch_tags(
+    db_connection: &mut Connection,
+    user: &User,
+) -> anyhow::Result<HashMap<String, Vec<String>>> {
+    let mut select_stmt = db_connection.prepare(
+        "SELECT 
+        guid, tag
+        FROM tags
+        WHERE guid IN (SELECT guid FROM articles WHERE user_id = ?)
+        ORDER BY tag ASC",
+    )?;
+
+    let mut rows = select_stmt.query([user.id])?;
+
+    let mut tags: HashMap<String, Vec<String>> = HashMap::new();
+    while let Some(row) = rows.next()? {
+        let guid: String = row.get(0)?;
+        tags.entry(guid).or_default().push(row.get(1)?);
+    }
+
+    Ok(tags)
+}
+
+/// Notes of the user's articles grouped by guid, oldest first
+fn fetch_notes(
+    db_connection: &mut Connection,
+    user: &User,
+) -> anyhow::Result<HashMap<String, Vec<Note>>> {
+    let mut select_stmt = db_connection.prepare(
+        "SELECT 
+        id, guid, kind, text
+        FROM notes
+        WHERE guid IN (SELECT guid FROM articles WHERE user_id = ?)
+        ORDER BY timestamp ASC, id ASC",
+    )?;
+
+    let mut rows = select_stmt.query([user.id])?;
+
+    let mut notes: HashMap<String, Vec<Note>> = HashMap::new();
+    while let Some(row) = rows.next()? {
+        let guid: String = row.get(1)?;
+        let kind: String = row.get(2)?;
+        let note = Note {
+            id: row.get(0)?,
+            kind: kind.parse()?,
+            text: row.get(3)?,
+        };
+        notes.entry(guid).or_default().push(note);
+    }
+
+    Ok(notes)
+}
+
+/// Articles whose time to live ends within the expiring window, soonest first
+fn fetch_expiring_feed(
+    db_connection: &mut Connection,
+    user: &User,
+    args: &Args,
+) -> anyhow::Result<Vec<FeedItem>> {
+    let mut select_stmt = db_connection.prepare(&format!(
+        "SELECT {FEED_ITEM_COLUMNS}
+        FROM articles
+        WHERE user_id = ? AND deleted_at IS NULL
+        AND (current_timestamp AT TIME ZONE 'UTC' - coalesce(extended_at, timestamp) AT TIME ZONE 'UTC') > INTERVAL (?) DAY
+        ORDER BY coalesce(extended_at, timestamp) ASC"
+    ))?;
+
+    let fresh_days = user.ttl.saturating_sub(args.expiring_window);
+    let rows = select_stmt.query(params![user.ttl, user.id, fresh_days])?;
+    read_feed_items(rows)
+}
+
+/// Columns of the articles read by `read_feed_items`, the first parameter is
+/// the user's time to live
+const FEED_ITEM_COLUMNS: &str = "title, link, guid,
+    strftime(timestamp AT TIME ZONE 'GMT', '%a, %d %b %Y %X GMT'),
+    strftime((coalesce(extended_at, timestamp) + INTERVAL (?) DAY) AT TIME ZONE 'GMT', '%a, %d %b %Y %X GMT'),
+    epoch_us(CAST(timestamp AS TIMESTAMP)),
+    coalesce(kind, 'other')";
+
+fn read_feed_items(mut rows: duckdb::Rows) -> anyhow::Result<Vec<FeedItem>> {
+    let count = rows.as_ref().unwrap().row_count();
 
     let mut items = Vec::with_capacity(count);
     while let Some(row) = rows.next()? {
+        let kind: String = row.get(6)?;
         let item = FeedItem {
             title: row.get(0)?,
             link: row.get(1)?,
             guid: row.get(2)?,
             pub_date: row.get(3)?,
+            expire_date: row.get(4)?,
+            pub_time: SystemTime::UNIX_EPOCH + Duration::from_micros(row.get(5)?),
+            kind: kind.parse()?,
+            tags: Vec::new(),
+            notes: Vec::new(),
+        };
+        items.push(item);
+    }
+
+    Ok(items)
+}
+
+fn fetch_trash(
+    db_connection: &mut Connection,
+    user: &User,
+    args: &Args,
+) -> anyhow::Result<Vec<TrashItem>> {
+    let mut select_stmt = db_connection.prepare(
+        "SELECT 
+        title, link, guid,
+        strftime(deleted_at AT TIME ZONE 'GMT', '%a, %d %b %Y %X GMT'),
+        strftime((deleted_at + INTERVAL (?) DAY) AT TIME ZONE 'GMT', '%a, %d %b %Y %X GMT')
+        FROM articles
+        WHERE user_id = ? AND deleted_at IS NOT NULL
+        ORDER BY deleted_at DESC",
+    )?;
+
+    let mut rows = select_stmt.query(params![args.trash_grace, user.id])?;
+    let count = rows.as_ref().unwrap().row_count();
+
+    let mut items = Vec::with_capacity(count);
+    while let Some(row) = rows.next()? {
+        let item = TrashItem {
+            title: row.get(0)?,
+            link: row.get(1)?,
+            guid: row.get(2)?,
+            deleted_date: row.get(3)?,
+            purge_date: row.get(4)?,
         };
         items.push(item);
     }
@@ -354,21 +2488,40 @@ fn fetch_feed(db_connection: &mut Connection) -> anyhow::Result<Vec<FeedItem>> {
     Ok(items)
 }
 
-fn fetch_weekly_stats(db_connection: &mut Connection) -> anyhow::Result<Vec<WeeklyItem>> {
+/// The user's activity of the last 52 weeks including the current one, oldest first
+fn fetch_weekly_stats(
+    db_connection: &mut Connection,
+    user: &User,
+) -> anyhow::Result<Vec<WeeklyItem>> {
     let mut select_stmt = db_connection.prepare(
         "SELECT 
-        articles_count
-        FROM stats_per_week_of_year
-        ORDER BY week_of_year ASC",
+        strftime(weeks.week, '%Y-%m-%d'),
+        coalesce(sum(activity.count) FILTER (WHERE activity.kind = 'saved'), 0),
+        coalesce(sum(activity.count) FILTER (WHERE activity.kind = 'deleted'), 0),
+        coalesce(sum(activity.count) FILTER (WHERE activity.kind = 'expired'), 0),
+        coalesce(sum(activity.count) FILTER (WHERE activity.kind = 'read'), 0)
+        FROM generate_series(
+            date_trunc('week', CAST(current_timestamp AS TIMESTAMP)) - INTERVAL 51 WEEK,
+            date_trunc('week', CAST(current_timestamp AS TIMESTAMP)),
+            INTERVAL 1 WEEK
+        ) AS weeks(week)
+        LEFT JOIN activity ON CAST(date_trunc('week', activity.day) AS TIMESTAMP) = weeks.week
+        AND activity.user_id = ?
+        GROUP BY weeks.week
+        ORDER BY weeks.week ASC",
     )?;
 
-    let mut rows = select_stmt.query([])?;
+    let mut rows = select_stmt.query([user.id])?;
     let count = rows.as_ref().unwrap().row_count();
 
     let mut items = Vec::with_capacity(count);
     while let Some(row) = rows.next()? {
         let item = WeeklyItem {
-            articles_count: i64::max(0, row.get(0)?),
+            week: row.get(0)?,
+            saved: row.get(1)?,
+            deleted: row.get(2)?,
+            expired: row.get(3)?,
+            read: row.get(4)?,
         };
         items.push(item);
     }
@@ -376,30 +2529,926 @@ fn fetch_weekly_stats(db_connection: &mut Connection) -> anyhow::Result<Vec<Week
     Ok(items)
 }
 
-// TODO the rss writer doesn't write weekly items. so api is dubious. type state writer?
 fn build_feed<T: FeedWriter>(
-    feed_items: Vec<FeedItem>,
+    mut writer: T,
+    (feed_items, pagination): (Vec<FeedItem>, Pagination),
+    expiring_items: Vec<FeedItem>,
     weekly_items: Vec<WeeklyItem>,
-    args: &Args,
+    expiring_window: u32,
+    filter: &FeedFilter,
+    undo_guid: Option<&str>,
 ) -> String {
-    let mut writer = T::new(
-        &args.name,
-        &args.desc,
-        args.link.as_str(),
-        SystemTime::now(),
-    );
+    if let Some(guid) = undo_guid {
+        writer.write_undo_notice(guid);
+    }
     writer.write_weekly_items(weekly_items);
+    writer.write_expiring_items(expiring_items, expiring_window);
+    writer.write_filter(filter);
     writer.write_feed_items(feed_items);
+    writer.write_pagination(&pagination);
     writer.finish()
 }
 
+/// Output of the feed as a page or in one of the feed formats. What only pages
+/// show is skipped by the feeds
 trait FeedWriter {
     const CONTENT_TYPE: &str;
+    /// Where the feed is served, relative to the link
+    const PATH: &str;
+
+    fn new(title: &str, description: &str, link: &str, time: SystemTime) -> Self
+    where
+        Self: Sized,
+    {
+        Self::new_at(title, description, link, Self::PATH, time)
+    }
 
-    fn new(title: &str, description: &str, link: &str, time: SystemTime) -> Self;
-
-    fn write_weekly_items(&mut self, items: Vec<WeeklyItem>);
+    /// Feed served at `path` rather than at `PATH`, for its self and page links
+    fn new_at(title: &str, description: &str, link: &str, path: &str, time: SystemTime) -> Self;
+
+    /// Token the HTML forms are posted with, feeds have no forms
+    fn set_csrf_token(&mut self, _token: &str) {}
+    /// Key signing the extend links of feed readers
+    fn set_extend_key(&mut self, _key: &ExtendKey) {}
+    /// Name of the user who saved the articles
+    fn set_author(&mut self, _name: &str) {}
+    fn write_undo_notice(&mut self, _guid: &str) {}
+    fn write_weekly_items(&mut self, _items: Vec<WeeklyItem>) {}
+    /// Articles ending within the next `window` days
+    fn write_expiring_items(&mut self, _items: Vec<FeedItem>, _window: u32) {}
     fn write_feed_items(&mut self, items: Vec<FeedItem>);
+    fn write_search_items(&mut self, query: &SearchQuery, items: Vec<FeedItem>);
+    fn write_filter(&mut self, _filter: &FeedFilter) {}
+    fn write_pagination(&mut self, pagination: &Pagination);
 
     fn finish(self) -> String;
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::Activity;
+    use crate::App;
+    use crate::Args;
+    use crate::Article;
+    use crate::BulkAction;
+    use crate::DocumentKind;
+    use crate::EditForm;
+    use crate::EditNoteForm;
+    use crate::ExtendLinkQuery;
+    use crate::InvalidEdit;
+    use crate::NoteForm;
+    use crate::NoteKind;
+    use crate::RefetchForm;
+    use crate::add_note;
+    use crate::bulk_update;
+    use crate::cache::FeedCache;
+    use crate::csrf::Csrf;
+    use crate::delete_article;
+    use crate::delete_note;
+    use crate::edit_article;
+    use crate::edit_note;
+    use crate::extend::ExtendKey;
+    use crate::extend_article;
+    use crate::fetch_feed_item;
+    use crate::fetch_feed_page;
+    use crate::fetch_last_change;
+    use crate::fetch_notes;
+    use crate::fetch_tags;
+    use crate::fetch_trashed_guid;
+    use crate::fetch_weekly_stats;
+    use crate::filter::FeedFilter;
+    use crate::handle_edit_article;
+    use crate::handle_edit_note;
+    use crate::handle_follow_extend_link;
+    use crate::handle_patch_article;
+    use crate::handle_refetch_article;
+    use crate::page::Page;
+    use crate::page::PageQuery;
+    use crate::parse_compression;
+    use crate::purge_trash;
+    use crate::read_article;
+    use crate::record_activity;
+    use crate::restore_article;
+    use crate::setup_tables;
+    use crate::store_article;
+    use crate::tag_article;
+    use crate::user::User;
+    use argh::FromArgs;
+    use axum::Extension;
+    use axum::Form;
+    use axum::extract::Query;
+    use axum::extract::State;
+    use axum::http::StatusCode;
+    use axum::http::header::LOCATION;
+    use axum::response::IntoResponse;
+    use duckdb::Connection;
+    use std::sync::Arc;
+    use std::sync::Mutex;
+    use url::Url;
+
+    pub(crate) fn user(id: i64) -> User {
+        User {
+            id,
+            name: format!("user{id}"),
+            ttl: 60,
+            feed_name: "My Paket".to_string(),
+            feed_desc: "My links".to_string(),
+        }
+    }
+
+    fn article(url: &str) -> Article {
+        Article {
+            url: Url::parse(url).unwrap(),
+            title: "Example".to_string(),
+            kind: DocumentKind::Html,
+        }
+    }
+
+    pub(crate) fn app() -> App {
+        let mut db_connection = Connection::open_in_memory().unwrap();
+        setup_tables(&mut db_connection).unwrap();
+        let extend_key = ExtendKey::load(&mut db_connection).unwrap();
+
+        App {
+            args: Arc::new(Args::from_args(&["paket"], &["-l", "https://paket.example/"]).unwrap()),
+            db_connection: Arc::new(Mutex::new(db_connection)),
+            feed_cache: Arc::new(Mutex::new(FeedCache::default())),
+            csrf: Arc::new(Csrf::new().unwrap()),
+            extend_key: Arc::new(extend_key),
+        }
+    }
+
+    fn edit(guid: &str) -> EditForm {
+        EditForm {
+            guid: guid.to_string(),
+            title: None,
+            link: None,
+            tags: None,
+            pinned: None,
+        }
+    }
+
+    /// Time zone arithmetic needs DuckDB's ICU extension, installed on first use.
+    /// Offline there is nothing to check
+    #[test]
+    fn weekly_stats_cover_rolling_year() {
+        let mut db_connection = Connection::open_in_memory().unwrap();
+        setup_tables(&mut db_connection).unwrap();
+
+        let tx = db_connection.transaction().unwrap();
+        record_activity(&tx, &user(0), Activity::Saved, 2).unwrap();
+        record_activity(&tx, &user(0), Activity::Saved, 1).unwrap();
+        record_activity(&tx, &user(0), Activity::Read, 1).unwrap();
+        record_activity(&tx, &user(0), Activity::Deleted, 0).unwrap();
+        record_activity(&tx, &user(1), Activity::Saved, 5).unwrap();
+        tx.execute(
+            "INSERT INTO activity (day, kind, count) VALUES (CAST(current_timestamp AS TIMESTAMP) - INTERVAL 400 DAY, 'saved', 7)",
+            [],
+        )
+        .unwrap();
+        tx.commit().unwrap();
+
+        let items = fetch_weekly_stats(&mut db_connection, &user(0)).unwrap();
+
+        assert_eq!(items.len(), 52);
+        assert_eq!(items.iter().map(|item| item.saved).sum::<i64>(), 3);
+
+        let this_week = items.last().unwrap();
+        assert_eq!(this_week.saved, 3);
+        assert_eq!(this_week.read, 1);
+        assert_eq!(this_week.deleted, 0);
+        assert_eq!(this_week.expired, 0);
+    }
+
+    #[test]
+    fn weekly_stats_are_kept_as_they_were() {
+        let mut db_connection = Connection::open_in_memory().unwrap();
+        db_connection
+            .execute_batch(
+                "CREATE TABLE stats_per_week_of_year (
+                    week_of_year INT64 NOT NULL PRIMARY KEY,
+                    articles_count INT64 NOT NULL);
+                INSERT INTO stats_per_week_of_year VALUES (1, 3), (2, 5), (3, 4);",
+            )
+            .unwrap();
+        setup_tables(&mut db_connection).unwrap();
+
+        let activity = db_connection
+            .query_row("SELECT count(*) FROM activity", [], |row| {
+                row.get::<_, i64>(0)
+            })
+            .unwrap();
+        assert_eq!(activity, 0);
+
+        let weekly_stats = db_connection
+            .query_row(
+                "SELECT count(*), sum(articles_count) FROM stats_per_week_of_year",
+                [],
+                |row| Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?)),
+            )
+            .unwrap();
+        assert_eq!(weekly_stats, (3, 12));
+    }
+
+    #[test]
+    fn reads_are_recorded_once() {
+        let mut db_connection = Connection::open_in_memory().unwrap();
+        setup_tables(&mut db_connection).unwrap();
+
+        let (user, other) = (user(0), user(1));
+        let guid =
+            store_article(&mut db_connection, &user, article("https://example.com/")).unwrap();
+
+        assert!(!read_article(&mut db_connection, &other, &guid).unwrap());
+        assert!(!read_article(&mut db_connection, &user, "unknown").unwrap());
+        assert!(read_article(&mut db_connection, &user, &guid).unwrap());
+        assert!(read_article(&mut db_connection, &user, &guid).unwrap());
+
+        let reads = db_connection
+            .query_row(
+                "SELECT sum(count) FROM activity
+                WHERE kind = 'read' AND day = CAST(make_timestamp(epoch_us(current_timestamp)) AS DATE)",
+                [],
+                |row| row.get::<_, i64>(0),
+            )
+            .unwrap();
+        assert_eq!(reads, 1);
+    }
+
+    #[test]
+    #[ignore = "feed dates need DuckDB's ICU extension, which isn't bundled"]
+    fn cursor_pages_through_articles_saved_together() {
+        let mut db_connection = Connection::open_in_memory().unwrap();
+        setup_tables(&mut db_connection).unwrap();
+
+        let user = user(0);
+        let mut guids = [
+            "https://example.com/a",
+            "https://example.com/b",
+            "https://example.com/c",
+        ]
+        .map(|url| store_article(&mut db_connection, &user, article(url)).unwrap())
+        .to_vec();
+        db_connection
+            .execute(
+                "UPDATE articles SET timestamp = TIMESTAMPTZ '2024-01-01 00:00:00+00'",
+                [],
+            )
+            .unwrap();
+
+        let mut page = Page::new(&PageQuery::default(), 1);
+        let mut seen = Vec::new();
+        loop {
+            let (items, pagination) =
+                fetch_feed_page(&mut db_connection, &user, &page, &FeedFilter::default()).unwrap();
+            seen.extend(items.into_iter().map(|item| item.guid));
+            let Some(next) = pagination.next else {
+                break;
+            };
+            let before = next
+                .trim_start_matches("?before=")
+                .split('&')
+                .next()
+                .unwrap();
+            page.before = Some(before.parse().unwrap());
+        }
+
+        guids.sort();
+        guids.reverse();
+        assert_eq!(seen, guids);
+    }
+
+    #[test]
+    #[ignore = "feed dates need DuckDB's ICU extension, which isn't bundled"]
+    fn single_article_is_live_and_own() {
+        let mut db_connection = Connection::open_in_memory().unwrap();
+        setup_tables(&mut db_connection).unwrap();
+
+        let (user, other) = (user(0), user(1));
+        let guid =
+            store_article(&mut db_connection, &user, article("https://example.com/")).unwrap();
+        let tx = db_connection.transaction().unwrap();
+        tag_article(&tx, &user, &guid, &["rust".to_string()]).unwrap();
+        tx.commit().unwrap();
+
+        let item = fetch_feed_item(&mut db_connection, &user, &guid)
+            .unwrap()
+            .unwrap();
+        assert_eq!(item.link, "https://example.com/");
+        assert_eq!(item.tags, vec!["rust".to_string()]);
+
+        assert!(
+            fetch_feed_item(&mut db_connection, &other, &guid)
+                .unwrap()
+                .is_none()
+        );
+        assert!(
+            fetch_feed_item(&mut db_connection, &user, "unknown")
+                .unwrap()
+                .is_none()
+        );
+
+        delete_article(&mut db_connection, &user, &guid).unwrap();
+        assert!(
+            fetch_feed_item(&mut db_connection, &user, &guid)
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn changes_advance_revision() {
+        let mut db_connection = Connection::open_in_memory().unwrap();
+        setup_tables(&mut db_connection).unwrap();
+
+        let initial = fetch_last_change(&mut db_connection).unwrap();
+
+        store_article(
+            &mut db_connection,
+            &user(0),
+            article("https://example.com/"),
+        )
+        .unwrap();
+        let stored = fetch_last_change(&mut db_connection).unwrap();
+        assert!(stored > initial);
+
+        delete_article(&mut db_connection, &user(0), "unknown").unwrap();
+        assert_eq!(fetch_last_change(&mut db_connection).unwrap(), stored);
+    }
+
+    #[test]
+    fn bulk_actions_count_changed_articles() {
+        let mut db_connection = Connection::open_in_memory().unwrap();
+        setup_tables(&mut db_connection).unwrap();
+
+        let user = user(0);
+        let mut guids = ["https://example.com/a", "https://example.com/b"]
+            .map(|url| store_article(&mut db_connection, &user, article(url)).unwrap())
+            .to_vec();
+        guids.push("unknown".to_string());
+
+        let read = bulk_update(&mut db_connection, &user, &guids, &BulkAction::Read).unwrap();
+        assert_eq!(read, 2);
+        let read = bulk_update(&mut db_connection, &user, &guids, &BulkAction::Read).unwrap();
+        assert_eq!(read, 0);
+
+        let tag = BulkAction::Tag {
+            tags: vec!["Rust".to_string(), "rust, web".to_string()],
+        };
+        assert_eq!(
+            bulk_update(&mut db_connection, &user, &guids, &tag).unwrap(),
+            2
+        );
+        let tags = db_connection
+            .query_row("SELECT count(*) FROM tags", [], |row| row.get::<_, i64>(0))
+            .unwrap();
+        assert_eq!(tags, 4);
+
+        let deleted = bulk_update(&mut db_connection, &user, &guids, &BulkAction::Delete).unwrap();
+        assert_eq!(deleted, 2);
+    }
+
+    #[test]
+    fn trashed_articles_are_not_read() {
+        let mut db_connection = Connection::open_in_memory().unwrap();
+        setup_tables(&mut db_connection).unwrap();
+
+        let user = user(0);
+        let guid =
+            store_article(&mut db_connection, &user, article("https://example.com/")).unwrap();
+        delete_article(&mut db_connection, &user, &guid).unwrap();
+
+        let guids = vec![guid.clone()];
+        let read = bulk_update(&mut db_connection, &user, &guids, &BulkAction::Read).unwrap();
+        assert_eq!(read, 0);
+        assert!(!read_article(&mut db_connection, &user, &guid).unwrap());
+
+        let is_read = db_connection
+            .query_row(
+                "SELECT read_at IS NOT NULL FROM articles WHERE guid = ?",
+                [&guid],
+                |row| row.get::<_, bool>(0),
+            )
+            .unwrap();
+        assert!(!is_read);
+    }
+
+    #[test]
+    fn edits_pin_and_unpin() {
+        let mut db_connection = Connection::open_in_memory().unwrap();
+        setup_tables(&mut db_connection).unwrap();
+
+        let guid = store_article(
+            &mut db_connection,
+            &user(0),
+            article("https://example.com/"),
+        )
+        .unwrap();
+
+        let mut pinned = |pinned: Option<bool>| {
+            let edit = EditForm {
+                guid: guid.clone(),
+                title: None,
+                link: None,
+                tags: None,
+                pinned,
+            };
+            edit_article(&mut db_connection, &user(0), &edit).unwrap();
+            db_connection
+                .query_row(
+                    "SELECT pinned_at IS NOT NULL FROM articles WHERE guid = ?",
+                    [&guid],
+                    |row| row.get::<_, bool>(0),
+                )
+                .unwrap()
+        };
+
+        assert!(!pinned(None));
+        assert!(pinned(Some(true)));
+        assert!(pinned(None));
+        assert!(!pinned(Some(false)));
+    }
+
+    #[test]
+    fn articles_belong_to_their_user() {
+        let mut db_connection = Connection::open_in_memory().unwrap();
+        setup_tables(&mut db_connection).unwrap();
+
+        let (default, alice) = (user(0), user(1));
+        let default_guid = store_article(
+            &mut db_connection,
+            &default,
+            article("https://example.com/"),
+        )
+        .unwrap();
+        let alice_guid =
+            store_article(&mut db_connection, &alice, article("https://example.com/")).unwrap();
+        assert_ne!(default_guid, alice_guid);
+
+        let edit = EditForm {
+            guid: alice_guid.clone(),
+            title: Some("Mine".to_string()),
+            link: None,
+            tags: Some("rust".to_string()),
+            pinned: None,
+        };
+        assert!(edit_article(&mut db_connection, &default, &edit).is_err());
+        edit_article(&mut db_connection, &alice, &edit).unwrap();
+        assert!(fetch_tags(&mut db_connection, &default).unwrap().is_empty());
+        assert_eq!(fetch_tags(&mut db_connection, &alice).unwrap().len(), 1);
+
+        let guids = [alice_guid];
+        let deleted =
+            bulk_update(&mut db_connection, &default, &guids, &BulkAction::Delete).unwrap();
+        assert_eq!(deleted, 0);
+
+        let articles = db_connection
+            .query_row(
+                "SELECT count(*) FROM articles WHERE deleted_at IS NULL",
+                [],
+                |row| row.get::<_, i64>(0),
+            )
+            .unwrap();
+        assert_eq!(articles, 2);
+    }
+
+    #[test]
+    fn extends_live_articles_of_user() {
+        let mut db_connection = Connection::open_in_memory().unwrap();
+        setup_tables(&mut db_connection).unwrap();
+
+        let (user, other) = (user(0), user(1));
+        let guids = ["https://example.com/a", "https://example.com/b"]
+            .map(|url| store_article(&mut db_connection, &user, article(url)).unwrap());
+        delete_article(&mut db_connection, &user, &guids[1]).unwrap();
+
+        let extended = |db_connection: &Connection| {
+            db_connection
+                .query_row(
+                    "SELECT count(*) FROM articles WHERE extended_at IS NOT NULL",
+                    [],
+                    |row| row.get::<_, i64>(0),
+                )
+                .unwrap()
+        };
+
+        for guid in &guids {
+            extend_article(&mut db_connection, &other, guid).unwrap();
+        }
+        assert_eq!(extended(&db_connection), 0);
+
+        for guid in &guids {
+            extend_article(&mut db_connection, &user, guid).unwrap();
+        }
+        assert_eq!(extended(&db_connection), 1);
+    }
+
+    #[test]
+    fn deleted_articles_wait_in_trash() {
+        let mut db_connection = Connection::open_in_memory().unwrap();
+        setup_tables(&mut db_connection).unwrap();
+
+        let (user, other) = (user(0), user(1));
+        let guid =
+            store_article(&mut db_connection, &user, article("https://example.com/")).unwrap();
+
+        delete_article(&mut db_connection, &other, &guid).unwrap();
+        assert_eq!(
+            fetch_trashed_guid(&mut db_connection, &user, Some(&guid)).unwrap(),
+            None
+        );
+
+        delete_article(&mut db_connection, &user, &guid).unwrap();
+        assert_eq!(
+            fetch_trashed_guid(&mut db_connection, &user, Some(&guid)).unwrap(),
+            Some(guid.clone())
+        );
+        assert_eq!(
+            fetch_trashed_guid(&mut db_connection, &other, Some(&guid)).unwrap(),
+            None
+        );
+        assert_eq!(
+            fetch_trashed_guid(&mut db_connection, &user, Some("unknown")).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    #[ignore = "restoring needs DuckDB's ICU extension, which isn't bundled"]
+    fn restored_articles_leave_trash() {
+        let mut db_connection = Connection::open_in_memory().unwrap();
+        setup_tables(&mut db_connection).unwrap();
+
+        let (user, other) = (user(0), user(1));
+        let guid =
+            store_article(&mut db_connection, &user, article("https://example.com/")).unwrap();
+        delete_article(&mut db_connection, &user, &guid).unwrap();
+
+        restore_article(&mut db_connection, &other, &guid).unwrap();
+        assert!(
+            fetch_trashed_guid(&mut db_connection, &user, Some(&guid))
+                .unwrap()
+                .is_some()
+        );
+        restore_article(&mut db_connection, &user, &guid).unwrap();
+        assert_eq!(
+            fetch_trashed_guid(&mut db_connection, &user, Some(&guid)).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    #[ignore = "purging needs DuckDB's ICU extension, which isn't bundled"]
+    fn purge_empties_trash_after_grace() {
+        let mut db_connection = Connection::open_in_memory().unwrap();
+        setup_tables(&mut db_connection).unwrap();
+        let args = Args::from_args(
+            &["paket"],
+            &["-l", "https://paket.example/", "--trash-grace", "14"],
+        )
+        .unwrap();
+
+        let user = user(0);
+        let guids = ["https://example.com/old", "https://example.com/new"]
+            .map(|url| store_article(&mut db_connection, &user, article(url)).unwrap());
+        for guid in &guids {
+            let edit = EditForm {
+                guid: guid.clone(),
+                title: None,
+                link: None,
+                tags: Some("rust".to_string()),
+                pinned: None,
+            };
+            edit_article(&mut db_connection, &user, &edit).unwrap();
+            delete_article(&mut db_connection, &user, guid).unwrap();
+        }
+        db_connection
+            .execute(
+                "UPDATE articles SET deleted_at = CAST(deleted_at AS TIMESTAMP) - INTERVAL 15 DAY WHERE guid = ?",
+                [&guids[0]],
+            )
+            .unwrap();
+
+        purge_trash(&mut db_connection, &args).unwrap();
+
+        let count = |db_connection: &Connection, table: &str, guid: &str| {
+            db_connection
+                .query_row(
+                    &format!("SELECT count(*) FROM {table} WHERE guid = ?"),
+                    [guid],
+                    |row| row.get::<_, i64>(0),
+                )
+                .unwrap()
+        };
+        assert_eq!(count(&db_connection, "articles", &guids[0]), 0);
+        assert_eq!(count(&db_connection, "tags", &guids[0]), 0);
+        assert_eq!(count(&db_connection, "articles", &guids[1]), 1);
+        assert_eq!(count(&db_connection, "tags", &guids[1]), 1);
+    }
+
+    #[test]
+    fn notes_are_added_edited_and_removed() {
+        let mut db_connection = Connection::open_in_memory().unwrap();
+        setup_tables(&mut db_connection).unwrap();
+
+        let (user, other) = (user(0), user(1));
+        let guid =
+            store_article(&mut db_connection, &user, article("https://example.com/")).unwrap();
+
+        let note = |kind, text: &str| NoteForm {
+            guid: guid.clone(),
+            kind,
+            text: text.to_string(),
+        };
+        add_note(
+            &mut db_connection,
+            &user,
+            &note(NoteKind::Highlight, " Quote "),
+        )
+        .unwrap();
+        assert!(add_note(&mut db_connection, &user, &note(NoteKind::Note, "  ")).is_err());
+        assert!(add_note(&mut db_connection, &other, &note(NoteKind::Note, "Mine")).is_err());
+
+        let notes = fetch_notes(&mut db_connection, &user).unwrap();
+        let [highlight] = notes[&guid].as_slice() else {
+            panic!("expected one note");
+        };
+        assert_eq!(highlight.kind, NoteKind::Highlight);
+        assert_eq!(highlight.text, "Quote");
+        let id = highlight.id;
+
+        let text = |db_connection: &mut Connection| {
+            fetch_notes(db_connection, &user)
+                .unwrap()
+                .remove(&guid)
+                .unwrap_or_default()
+                .into_iter()
+                .map(|note| note.text)
+                .collect::<Vec<_>>()
+        };
+
+        edit_note(&mut db_connection, &other, id, "Changed").unwrap();
+        assert_eq!(text(&mut db_connection), ["Quote"]);
+        edit_note(&mut db_connection, &user, id, "Changed").unwrap();
+        assert_eq!(text(&mut db_connection), ["Changed"]);
+        delete_note(&mut db_connection, &other, id).unwrap();
+        assert_eq!(text(&mut db_connection), ["Changed"]);
+        edit_note(&mut db_connection, &user, id, " ").unwrap();
+        assert!(text(&mut db_connection).is_empty());
+
+        add_note(&mut db_connection, &user, &note(NoteKind::Note, "Again")).unwrap();
+        let id = fetch_notes(&mut db_connection, &user).unwrap()[&guid][0].id;
+        delete_note(&mut db_connection, &user, id).unwrap();
+        assert!(text(&mut db_connection).is_empty());
+
+        assert_eq!("note".parse::<NoteKind>().unwrap(), NoteKind::Note);
+        assert!("comment".parse::<NoteKind>().is_err());
+    }
+
+    #[test]
+    fn edits_replace_title_link_and_tags() {
+        let mut db_connection = Connection::open_in_memory().unwrap();
+        setup_tables(&mut db_connection).unwrap();
+
+        let user = user(0);
+        let guid =
+            store_article(&mut db_connection, &user, article("https://example.com/")).unwrap();
+
+        let stored = |db_connection: &Connection| {
+            db_connection
+                .query_row(
+                    "SELECT title, link FROM articles WHERE guid = ?",
+                    [&guid],
+                    |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)),
+                )
+                .unwrap()
+        };
+
+        let edited = EditForm {
+            title: Some(" Renamed ".to_string()),
+            link: Some("https://example.com/moved".to_string()),
+            tags: Some("Rust, web, rust".to_string()),
+            ..edit(&guid)
+        };
+        edit_article(&mut db_connection, &user, &edited).unwrap();
+        assert_eq!(
+            stored(&db_connection),
+            (
+                "Renamed".to_string(),
+                "https://example.com/moved".to_string()
+            )
+        );
+        assert_eq!(
+            fetch_tags(&mut db_connection, &user).unwrap()[&guid],
+            ["rust", "web"]
+        );
+
+        let blank = EditForm {
+            title: Some(" ".to_string()),
+            link: Some(String::new()),
+            tags: Some("news".to_string()),
+            ..edit(&guid)
+        };
+        edit_article(&mut db_connection, &user, &blank).unwrap();
+        assert_eq!(stored(&db_connection).0, "Renamed");
+        assert_eq!(
+            fetch_tags(&mut db_connection, &user).unwrap()[&guid],
+            ["news"]
+        );
+
+        let invalid = EditForm {
+            link: Some("ftp://example.com/".to_string()),
+            ..edit(&guid)
+        };
+        assert!(edit_article(&mut db_connection, &user, &invalid).is_err());
+        assert!(edit_article(&mut db_connection, &user, &edit("unknown")).is_err());
+
+        let other =
+            store_article(&mut db_connection, &user, article("https://example.org/")).unwrap();
+        let taken = EditForm {
+            link: Some("https://example.com/moved".to_string()),
+            ..edit(&other)
+        };
+        let err = edit_article(&mut db_connection, &user, &taken).unwrap_err();
+        assert!(err.downcast_ref::<InvalidEdit>().is_some());
+        assert_eq!(stored(&db_connection).1, "https://example.com/moved");
+    }
+
+    #[test]
+    fn saving_edited_link_keeps_notes_and_tags() {
+        let mut db_connection = Connection::open_in_memory().unwrap();
+        setup_tables(&mut db_connection).unwrap();
+
+        let user = user(0);
+        let guid =
+            store_article(&mut db_connection, &user, article("https://example.com/")).unwrap();
+        let note = NoteForm {
+            guid: guid.clone(),
+            kind: NoteKind::Note,
+            text: "Worth it".to_string(),
+        };
+        add_note(&mut db_connection, &user, &note).unwrap();
+        let moved = EditForm {
+            link: Some("https://example.com/moved".to_string()),
+            tags: Some("rust".to_string()),
+            pinned: Some(true),
+            ..edit(&guid)
+        };
+        edit_article(&mut db_connection, &user, &moved).unwrap();
+
+        let saved = store_article(
+            &mut db_connection,
+            &user,
+            article("https://example.com/moved"),
+        )
+        .unwrap();
+        assert_eq!(saved, guid);
+
+        let articles = db_connection
+            .query_row(
+                "SELECT count(*), bool_and(pinned_at IS NOT NULL) FROM articles",
+                [],
+                |row| Ok((row.get::<_, i64>(0)?, row.get::<_, bool>(1)?)),
+            )
+            .unwrap();
+        assert_eq!(articles, (1, true));
+        assert_eq!(
+            fetch_notes(&mut db_connection, &user).unwrap()[&guid].len(),
+            1
+        );
+        assert_eq!(
+            fetch_tags(&mut db_connection, &user).unwrap()[&guid],
+            ["rust"]
+        );
+    }
+
+    #[tokio::test]
+    async fn signed_extend_links_act_as_owner() {
+        let app = app();
+        let guid = {
+            let mut db_lock = app.db_connection.lock().unwrap();
+            store_article(&mut db_lock, &user(0), article("https://example.com/")).unwrap()
+        };
+        let query = |guid: &str, token: Option<&str>| {
+            let link = app.extend_key.link("https://paket.example/", guid).unwrap();
+            let token = token.map(ToString::to_string).unwrap_or_else(|| {
+                link.query_pairs()
+                    .find(|(name, _)| name == "token")
+                    .map(|(_, token)| token.into_owned())
+                    .unwrap()
+            });
+            Query(ExtendLinkQuery {
+                guid: guid.to_string(),
+                token,
+            })
+        };
+        let is_extended = |app: &App| {
+            app.db_connection
+                .lock()
+                .unwrap()
+                .query_row("SELECT extended_at IS NOT NULL FROM articles", [], |row| {
+                    row.get::<_, bool>(0)
+                })
+                .unwrap()
+        };
+
+        let forged = handle_follow_extend_link(State(app.clone()), query(&guid, Some("00"))).await;
+        assert_eq!(forged.unwrap_err(), StatusCode::FORBIDDEN);
+        assert!(!is_extended(&app));
+
+        let unknown = handle_follow_extend_link(State(app.clone()), query("unknown", None)).await;
+        assert_eq!(unknown.unwrap_err(), StatusCode::NOT_FOUND);
+
+        let response = handle_follow_extend_link(State(app.clone()), query(&guid, None))
+            .await
+            .into_response();
+        assert_eq!(response.headers()[LOCATION], "/feed.html");
+        assert!(is_extended(&app));
+    }
+
+    #[tokio::test]
+    async fn edit_routes_change_own_articles() {
+        let app = app();
+        let (user, other) = (user(0), user(1));
+        let guid = {
+            let mut db_lock = app.db_connection.lock().unwrap();
+            let guid = store_article(&mut db_lock, &user, article("https://example.com/")).unwrap();
+            let note = NoteForm {
+                guid: guid.clone(),
+                kind: NoteKind::Note,
+                text: "First".to_string(),
+            };
+            add_note(&mut db_lock, &user, &note).unwrap();
+            guid
+        };
+        let title = |app: &App| {
+            app.db_connection
+                .lock()
+                .unwrap()
+                .query_row("SELECT title FROM articles", [], |row| {
+                    row.get::<_, String>(0)
+                })
+                .unwrap()
+        };
+
+        let patch = EditForm {
+            title: Some("Patched".to_string()),
+            ..edit(&guid)
+        };
+        let status =
+            handle_patch_article(State(app.clone()), Extension(user.clone()), Form(patch)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(title(&app), "Patched");
+
+        let patch = EditForm {
+            title: Some("Theirs".to_string()),
+            ..edit(&guid)
+        };
+        let status =
+            handle_patch_article(State(app.clone()), Extension(other.clone()), Form(patch)).await;
+        assert_ne!(status, StatusCode::OK);
+        assert_eq!(title(&app), "Patched");
+
+        let form = EditForm {
+            title: Some("Edited".to_string()),
+            tags: Some("rust".to_string()),
+            ..edit(&guid)
+        };
+        let response = handle_edit_article(State(app.clone()), Extension(user.clone()), Form(form))
+            .await
+            .into_response();
+        assert_eq!(response.headers()[LOCATION], "/feed.html");
+        assert_eq!(title(&app), "Edited");
+
+        let id = fetch_notes(&mut app.db_connection.lock().unwrap(), &user).unwrap()[&guid][0].id;
+        let note = EditNoteForm {
+            id,
+            text: "Second".to_string(),
+        };
+        let response = handle_edit_note(State(app.clone()), Extension(user.clone()), Form(note))
+            .await
+            .into_response();
+        assert_eq!(response.headers()[LOCATION], "/feed.html");
+        let notes = fetch_notes(&mut app.db_connection.lock().unwrap(), &user).unwrap();
+        assert_eq!(notes[&guid][0].text, "Second");
+
+        // another user's article is not fetched at all
+        let refetch = RefetchForm { guid: guid.clone() };
+        let response = handle_refetch_article(State(app.clone()), Extension(other), Form(refetch))
+            .await
+            .into_response();
+        assert_eq!(response.headers()[LOCATION], "/feed.html");
+        assert_eq!(title(&app), "Edited");
+    }
+
+    #[test]
+    fn compression_options() {
+        let compression = parse_compression("gzip, zstd").unwrap();
+        assert!(compression.gzip && !compression.br && compression.zstd);
+
+        let compression = parse_compression("none").unwrap();
+        assert!(!compression.gzip && !compression.br && !compression.zstd);
+
+        assert!(parse_compression("deflate").is_err());
+    }
+}
@@ -0,0 +1,107 @@
+/// Buffer for XML and HTML output. Markup can only come from string literals,
+/// everything else is escaped on the way in
+pub struct Markup {
+    buffer: String,
+}
+
+impl Markup {
+    pub fn new() -> Self {
+        Self {
+            buffer: String::new(),
+        }
+    }
+
+    pub fn push_raw(&mut self, markup: &'static str) {
+        self.buffer.push_str(markup);
+    }
+
+    /// Escaped for both element content and quoted attribute values
+    pub fn push_text(&mut self, text: &str) {
+        for c in text.chars() {
+            match c {
+                '&' => self.buffer.push_str("&amp;"),
+                '<' => self.buffer.push_str("&lt;"),
+                '>' => self.buffer.push_str("&gt;"),
+                '"' => self.buffer.push_str("&quot;"),
+                '\'' => self.buffer.push_str("&#39;"),
+                // not allowed in XML 1.0
+                '\u{0}'..='\u{8}' | '\u{b}' | '\u{c}' | '\u{e}'..='\u{1f}' => {}
+                c => self.buffer.push(c),
+            }
+        }
+    }
+
+    /// Like `push_text`, but links other than http(s) or relative ones are replaced with `#`
+    pub fn push_url(&mut self, url: &str) {
+        if is_safe_url(url) {
+            self.push_text(url.trim());
+        } else {
+            self.buffer.push('#');
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.buffer
+    }
+
+    pub fn into_string(self) -> String {
+        self.buffer
+    }
+}
+
+fn is_safe_url(url: &str) -> bool {
+    let url = url.trim();
+
+    if url.starts_with('/') || url.starts_with('?') || url.starts_with('#') {
+        return true;
+    }
+
+    let Some((scheme, _)) = url.split_once(':') else {
+        // no scheme, relative path
+        return !url.contains(|c: char| c.is_ascii_control());
+    };
+
+    scheme.eq_ignore_ascii_case("http") || scheme.eq_ignore_ascii_case("https")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::markup::Markup;
+
+    #[test]
+    fn text_is_escaped() {
+        let mut markup = Markup::new();
+        markup.push_raw("<p title=\"");
+        markup.push_text("\"><script>alert('x')</script>");
+        markup.push_raw("\">");
+        markup.push_text("AT&T <3 you\u{1}");
+        markup.push_raw("</p>");
+
+        assert_eq!(
+            markup.into_string(),
+            "<p title=\"&quot;&gt;&lt;script&gt;alert(&#39;x&#39;)&lt;/script&gt;\">AT&amp;T &lt;3 you</p>"
+        );
+    }
+
+    #[test]
+    fn unsafe_urls_are_replaced() {
+        let urls = [
+            (
+                "https://example.com/?a=1&b=2",
+                "https://example.com/?a=1&amp;b=2",
+            ),
+            ("HTTP://example.com", "HTTP://example.com"),
+            ("/read?guid=1", "/read?guid=1"),
+            ("javascript:alert(1)", "#"),
+            (" JavaScript:alert(1)", "#"),
+            ("data:text/html,<script>", "#"),
+            ("vbscript:msgbox", "#"),
+        ];
+
+        for (url, expected) in urls {
+            let mut markup = Markup::new();
+            markup.push_url(url);
+            assert_eq!(markup.into_string(), expected, "{url}");
+        }
+    }
+}
@@ -0,0 +1,223 @@
+use serde::Deserialize;
+use serde::Deserializer;
+use serde::de::Error;
+use std::fmt;
+use std::str::FromStr;
+use url::Url;
+use uuid::Uuid;
+
+/// Largest number of items a client may ask for at once
+const MAX_LIMIT: u32 = 500;
+
+/// `?limit=` with either a `?page=` number or a `?before=` cursor
+#[derive(Debug, Default, Deserialize)]
+pub struct PageQuery {
+    limit: Option<u32>,
+    page: Option<u32>,
+    #[serde(default, deserialize_with = "parse_cursor")]
+    before: Option<Cursor>,
+}
+
+/// The oldest article already seen, as `<save time in microseconds>_<guid>`.
+/// Articles saved at the same time are told apart by their guids
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Cursor {
+    pub time: i64,
+    pub guid: Uuid,
+}
+
+impl FromStr for Cursor {
+    type Err = String;
+
+    /// A save time alone, as in links from before the guid was added, comes
+    /// before every article saved at that time
+    fn from_str(cursor: &str) -> Result<Self, Self::Err> {
+        let (time, guid) = match cursor.split_once('_') {
+            Some((time, guid)) => (time, Some(guid)),
+            None => (cursor, None),
+        };
+        let time = time
+            .parse()
+            .map_err(|_| format!("invalid cursor time {time}"))?;
+        let guid = match guid {
+            Some(guid) => {
+                Uuid::parse_str(guid).map_err(|_| format!("invalid cursor guid {guid}"))?
+            }
+            None => Uuid::nil(),
+        };
+        Ok(Self { time, guid })
+    }
+}
+
+impl fmt::Display for Cursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}_{}", self.time, self.guid)
+    }
+}
+
+fn parse_cursor<'de, D>(deserializer: D) -> Result<Option<Cursor>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let value = Option::<String>::deserialize(deserializer)?;
+    value
+        .map(|value| value.parse().map_err(D::Error::custom))
+        .transpose()
+}
+
+/// Slice of the feed to fetch, newest first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Page {
+    pub limit: u32,
+    pub number: u32,
+    pub before: Option<Cursor>,
+}
+
+impl Page {
+    pub fn new(query: &PageQuery, default_limit: u32) -> Self {
+        Self {
+            limit: query.limit.unwrap_or(default_limit).clamp(1, MAX_LIMIT),
+            number: query.page.unwrap_or(1).max(1),
+            before: query.before,
+        }
+    }
+
+    /// Articles to skip. A cursor starts right after the article it points at
+    pub fn offset(&self) -> u64 {
+        match self.before {
+            Some(_) => 0,
+            None => u64::from(self.number - 1) * u64::from(self.limit),
+        }
+    }
+}
+
+/// Query strings of the neighbouring pages, as in RFC 5005 paged feeds
+#[derive(Debug, PartialEq)]
+pub struct Pagination {
+    pub first: String,
+    pub previous: Option<String>,
+    pub next: Option<String>,
+    pub last: Option<String>,
+}
+
+impl Pagination {
+    /// `count` articles from the start of the page on, for a cursor, or in total otherwise.
+    /// `oldest` points at the last article on the page.
+    /// `query` is appended to every link
+    pub fn new(page: &Page, count: u64, oldest: Option<Cursor>, query: &str) -> Self {
+        let limit = page.limit;
+        let first = format!("?page=1&limit={limit}{query}");
+
+        if page.before.is_some() {
+            let next = oldest
+                .filter(|_| count > u64::from(limit))
+                .map(|oldest| format!("?before={oldest}&limit={limit}{query}"));
+
+            return Self {
+                first,
+                previous: None,
+                next,
+                last: None,
+            };
+        }
+
+        let last_number = count.div_ceil(u64::from(limit)).max(1);
+        let number = u64::from(page.number);
+
+        Self {
+            first,
+            previous: (number > 1).then(|| {
+                format!(
+                    "?page={}&limit={limit}{query}",
+                    (number - 1).min(last_number)
+                )
+            }),
+            next: (number < last_number)
+                .then(|| format!("?page={}&limit={limit}{query}", number + 1)),
+            last: Some(format!("?page={last_number}&limit={limit}{query}")),
+        }
+    }
+}
+
+/// Absolute link to a page of the feed at `path`
+pub fn page_link(link: &str, path: &str, query: &str) -> Option<Url> {
+    Url::parse(link).ok()?.join(&format!("{path}{query}")).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::page::Cursor;
+    use crate::page::Page;
+    use crate::page::PageQuery;
+    use crate::page::Pagination;
+    use crate::page::page_link;
+
+    #[test]
+    fn numbered_pages() {
+        let query = PageQuery {
+            limit: Some(10),
+            page: Some(2),
+            before: None,
+        };
+        let page = Page::new(&query, 50);
+        assert_eq!(page.offset(), 10);
+
+        let pagination = Pagination::new(&page, 25, None, "&sort=title");
+        assert_eq!(pagination.first, "?page=1&limit=10&sort=title");
+        assert_eq!(
+            pagination.previous.as_deref(),
+            Some("?page=1&limit=10&sort=title")
+        );
+        assert_eq!(
+            pagination.next.as_deref(),
+            Some("?page=3&limit=10&sort=title")
+        );
+        assert_eq!(
+            pagination.last.as_deref(),
+            Some("?page=3&limit=10&sort=title")
+        );
+
+        let page = Page::new(&PageQuery::default(), 50);
+        let pagination = Pagination::new(&page, 0, None, "");
+        assert_eq!(page.offset(), 0);
+        assert_eq!(pagination.previous, None);
+        assert_eq!(pagination.next, None);
+        assert_eq!(pagination.last.as_deref(), Some("?page=1&limit=50"));
+    }
+
+    #[test]
+    fn cursor_pages() {
+        let guid = "6ba7b810-9dad-11d1-80b4-00c04fd430c8";
+        let oldest: Cursor = format!("900_{guid}").parse().unwrap();
+        assert_eq!(oldest.time, 900);
+        assert_eq!(oldest.guid.to_string(), guid);
+
+        let query = PageQuery {
+            limit: Some(0),
+            page: Some(7),
+            before: Some("1000".parse().unwrap()),
+        };
+        let page = Page::new(&query, 50);
+        assert_eq!(page.limit, 1);
+        assert_eq!(page.offset(), 0);
+        assert!(page.before.unwrap().guid.is_nil());
+
+        let pagination = Pagination::new(&page, 2, Some(oldest), "");
+        assert_eq!(pagination.next, Some(format!("?before=900_{guid}&limit=1")));
+        assert_eq!(pagination.previous, None);
+        assert_eq!(pagination.last, None);
+
+        let pagination = Pagination::new(&page, 1, Some(oldest), "");
+        assert_eq!(pagination.next, None);
+
+        assert!("900_guid".parse::<Cursor>().is_err());
+        assert!("later".parse::<Cursor>().is_err());
+
+        assert_eq!(
+            page_link("https://paket.example/", "feed.xml", "?page=2&limit=1")
+                .unwrap()
+                .as_str(),
+            "https://paket.example/feed.xml?page=2&limit=1"
+        );
+    }
+}
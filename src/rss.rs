@@ -1,84 +1,515 @@
+use crate::DocumentKind;
 use crate::FeedItem;
 use crate::FeedWriter;
-use crate::WeeklyItem;
+use crate::collection::CollectionItem;
+use crate::extend::ExtendKey;
+use crate::markup::Markup;
+use crate::page::Pagination;
+use crate::page::page_link;
+use crate::push_notes;
+use crate::search::SearchQuery;
 use httpdate::fmt_http_date;
 use std::time::SystemTime;
+use url::Url;
+
+/// How long readers may cache the feed, in minutes
+const TTL_MINUTES: u32 = 60;
 
 pub struct RssWriter {
-    buffer: String,
+    buffer: Markup,
+    link: String,
+    path: String,
+    extend_key: Option<ExtendKey>,
+    author: Option<String>,
+}
+
+impl RssWriter {
+    /// Items of a shared collection are credited to the member who saved them and
+    /// can't be extended by readers
+    pub fn write_collection_items(&mut self, items: Vec<CollectionItem>) {
+        let buffer = &mut self.buffer;
+
+        for item in items {
+            buffer.push_raw("<item>");
+
+            buffer.push_raw("<title>");
+            buffer.push_text(&item.title);
+            buffer.push_raw("</title>");
+
+            buffer.push_raw("<link>");
+            buffer.push_url(&item.link);
+            buffer.push_raw("</link>");
+
+            buffer.push_raw("<pubDate>");
+            buffer.push_text(&item.pub_date);
+            buffer.push_raw("</pubDate>");
+
+            if !item.saved_by.is_empty() {
+                buffer.push_raw("<dc:creator>");
+                buffer.push_text(&item.saved_by);
+                buffer.push_raw("</dc:creator>");
+            }
+
+            write_source(buffer, &item.link);
+            write_enclosure(buffer, &item.link, item.kind);
+
+            buffer.push_raw("<guid isPermaLink=\"false\">");
+            buffer.push_text(&item.guid);
+            buffer.push_raw("</guid>");
+
+            buffer.push_raw("</item>");
+        }
+    }
 }
 
 impl FeedWriter for RssWriter {
     const CONTENT_TYPE: &str = "application/rss+xml";
+    const PATH: &str = "feed.xml";
 
-    fn new(title: &str, description: &str, link: &str, time: SystemTime) -> Self {
-        let mut buffer = String::new();
+    fn new_at(title: &str, description: &str, link: &str, path: &str, time: SystemTime) -> Self {
+        let mut buffer = Markup::new();
 
         let date = fmt_http_date(time);
 
-        buffer.push_str("<rss version=\"2.0\">");
-        buffer.push_str("<channel>");
+        buffer.push_raw("<?xml version=\"1.0\" encoding=\"utf-8\"?>");
+        buffer.push_raw("<rss version=\"2.0\" xmlns:atom=\"http://www.w3.org/2005/Atom\" xmlns:content=\"http://purl.org/rss/1.0/modules/content/\" xmlns:dc=\"http://purl.org/dc/elements/1.1/\">");
+        buffer.push_raw("<channel>");
 
-        buffer.push_str("<title>");
-        buffer.push_str(title);
-        buffer.push_str("</title>");
+        buffer.push_raw("<title>");
+        buffer.push_text(title);
+        buffer.push_raw("</title>");
 
-        buffer.push_str("<description>");
-        buffer.push_str(description);
-        buffer.push_str("</description>");
+        buffer.push_raw("<description>");
+        buffer.push_text(description);
+        buffer.push_raw("</description>");
 
-        buffer.push_str("<link>");
-        buffer.push_str(link);
-        buffer.push_str("</link>");
+        buffer.push_raw("<link>");
+        buffer.push_url(link);
+        buffer.push_raw("</link>");
 
-        buffer.push_str("<pubDate>");
-        buffer.push_str(date.as_str());
-        buffer.push_str("</pubDate>");
+        if let Some(self_link) = Url::parse(link).ok().and_then(|url| url.join(path).ok()) {
+            buffer.push_raw("<atom:link rel=\"self\" type=\"application/rss+xml\" href=\"");
+            buffer.push_url(self_link.as_str());
+            buffer.push_raw("\"/>");
+        }
 
-        buffer.push_str("<lastBuildDate>");
-        buffer.push_str(date.as_str());
-        buffer.push_str("</lastBuildDate>");
+        buffer.push_raw("<pubDate>");
+        buffer.push_text(&date);
+        buffer.push_raw("</pubDate>");
 
-        buffer.push_str("<ttl>0</ttl>");
+        buffer.push_raw("<lastBuildDate>");
+        buffer.push_text(&date);
+        buffer.push_raw("</lastBuildDate>");
 
-        Self { buffer }
-    }
+        buffer.push_raw("<ttl>");
+        buffer.push_text(&TTL_MINUTES.to_string());
+        buffer.push_raw("</ttl>");
 
-    fn write_weekly_items(&mut self, _: Vec<WeeklyItem>) { /* noop */
+        Self {
+            buffer,
+            link: link.to_string(),
+            path: path.to_string(),
+            extend_key: None,
+            author: None,
+        }
     }
 
     fn write_feed_items(&mut self, items: Vec<FeedItem>) {
         let buffer = &mut self.buffer;
 
         for item in items {
-            buffer.push_str("<item>");
+            buffer.push_raw("<item>");
 
-            buffer.push_str("<title>");
-            buffer.push_str(item.title.as_str());
-            buffer.push_str("</title>");
+            buffer.push_raw("<title>");
+            buffer.push_text(&item.title);
+            buffer.push_raw("</title>");
 
-            buffer.push_str("<link>");
-            buffer.push_str(item.link.as_str());
-            buffer.push_str("</link>");
+            buffer.push_raw("<link>");
+            buffer.push_url(&item.link);
+            buffer.push_raw("</link>");
 
-            buffer.push_str("<pubDate>");
-            buffer.push_str(item.pub_date.as_str());
-            buffer.push_str("</pubDate>");
+            buffer.push_raw("<pubDate>");
+            buffer.push_text(&item.pub_date);
+            buffer.push_raw("</pubDate>");
 
-            buffer.push_str("<guid>");
-            buffer.push_str(item.guid.as_str());
-            buffer.push_str("</guid>");
+            let mut description = Markup::new();
+            description.push_raw("Expires: ");
+            description.push_text(&item.expire_date);
+            if let Some(extend_link) = self
+                .extend_key
+                .as_ref()
+                .and_then(|key| key.link(&self.link, &item.guid))
+            {
+                description.push_raw(" <a href=\"");
+                description.push_url(extend_link.as_str());
+                description.push_raw("\">Extend</a>");
+            }
+            // readers that ignore content:encoded only show the description
+            push_notes(&mut description, &item.notes);
 
-            buffer.push_str("</item>");
+            buffer.push_raw("<description>");
+            buffer.push_text(description.as_str());
+            buffer.push_raw("</description>");
+
+            if !item.notes.is_empty() {
+                let mut content = Markup::new();
+                push_notes(&mut content, &item.notes);
+
+                buffer.push_raw("<content:encoded>");
+                buffer.push_text(content.as_str());
+                buffer.push_raw("</content:encoded>");
+            }
+
+            if let Some(author) = &self.author {
+                buffer.push_raw("<dc:creator>");
+                buffer.push_text(author);
+                buffer.push_raw("</dc:creator>");
+            }
+
+            for tag in &item.tags {
+                buffer.push_raw("<category>");
+                buffer.push_text(tag);
+                buffer.push_raw("</category>");
+            }
+
+            write_source(buffer, &item.link);
+            write_enclosure(buffer, &item.link, item.kind);
+
+            buffer.push_raw("<guid isPermaLink=\"false\">");
+            buffer.push_text(&item.guid);
+            buffer.push_raw("</guid>");
+
+            buffer.push_raw("</item>");
+        }
+    }
+
+    fn write_search_items(&mut self, _: &SearchQuery, items: Vec<FeedItem>) {
+        self.write_feed_items(items);
+    }
+
+    fn set_extend_key(&mut self, key: &ExtendKey) {
+        self.extend_key = Some(key.clone());
+    }
+
+    fn set_author(&mut self, name: &str) {
+        self.author = Some(name.to_string());
+    }
+
+    fn write_pagination(&mut self, pagination: &Pagination) {
+        let links = [
+            ("first", Some(&pagination.first)),
+            ("previous", pagination.previous.as_ref()),
+            ("next", pagination.next.as_ref()),
+            ("last", pagination.last.as_ref()),
+        ];
+
+        let buffer = &mut self.buffer;
+
+        for (rel, query) in links {
+            let Some(url) = query.and_then(|query| page_link(&self.link, &self.path, query)) else {
+                continue;
+            };
+
+            buffer.push_raw("<atom:link rel=\"");
+            buffer.push_raw(rel);
+            buffer.push_raw("\" type=\"application/rss+xml\" href=\"");
+            buffer.push_url(url.as_str());
+            buffer.push_raw("\"/>");
         }
     }
 
     fn finish(mut self) -> String {
         let buffer = &mut self.buffer;
 
-        buffer.push_str("</channel>");
-        buffer.push_str("</rss>");
+        buffer.push_raw("</channel>");
+        buffer.push_raw("</rss>");
+
+        self.buffer.into_string()
+    }
+}
+
+/// The site the article was saved from
+fn write_source(buffer: &mut Markup, link: &str) {
+    let Some((site, host)) = source(link) else {
+        return;
+    };
+
+    buffer.push_raw("<source url=\"");
+    buffer.push_url(site.as_str());
+    buffer.push_raw("\">");
+    buffer.push_text(&host);
+    buffer.push_raw("</source>");
+}
+
+/// Origin of the site the article was saved from, and its host name
+fn source(link: &str) -> Option<(Url, String)> {
+    let url = Url::parse(link).ok()?;
+    let host = url.host_str()?.to_string();
+    Some((url.join("/").ok()?, host))
+}
+
+/// The file itself for documents and media. Its size is unknown as the file is
+/// not stored, so there is no length
+fn write_enclosure(buffer: &mut Markup, link: &str, kind: DocumentKind) {
+    // fetched PDFs are documents whatever their link looks like
+    let media_type = match kind {
+        DocumentKind::Pdf => Some("application/pdf"),
+        DocumentKind::Html | DocumentKind::Other => enclosure_type(link),
+    };
+    let Some(media_type) = media_type else {
+        return;
+    };
+
+    buffer.push_raw("<enclosure url=\"");
+    buffer.push_url(link);
+    buffer.push_raw("\" type=\"");
+    buffer.push_raw(media_type);
+    buffer.push_raw("\"/>");
+}
+
+/// Media type for links pointing directly at documents or media files
+pub fn enclosure_type(link: &str) -> Option<&'static str> {
+    let url = Url::parse(link).ok()?;
+    let extension = url.path().rsplit_once('.')?.1.to_ascii_lowercase();
+
+    let media_type = match extension.as_str() {
+        "pdf" => "application/pdf",
+        "epub" => "application/epub+zip",
+        "mp3" => "audio/mpeg",
+        "m4a" => "audio/mp4",
+        "ogg" | "oga" => "audio/ogg",
+        "wav" => "audio/wav",
+        "mp4" | "m4v" => "video/mp4",
+        "webm" => "video/webm",
+        "mov" => "video/quicktime",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        _ => return None,
+    };
+
+    Some(media_type)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::DocumentKind;
+    use crate::FeedItem;
+    use crate::FeedWriter;
+    use crate::Note;
+    use crate::NoteKind;
+    use crate::collection::CollectionItem;
+    use crate::extend::ExtendKey;
+    use crate::page::Page;
+    use crate::page::Pagination;
+    use crate::rss::RssWriter;
+    use crate::setup_tables;
+    use duckdb::Connection;
+    use std::time::SystemTime;
+
+    #[test]
+    fn hostile_items_are_escaped() {
+        let mut writer = RssWriter::new(
+            "<My> Paket",
+            "Links & more",
+            "https://paket.example/",
+            SystemTime::UNIX_EPOCH,
+        );
+        writer.write_feed_items(vec![FeedItem {
+            title: "AT&T <3 you".to_string(),
+            link: "javascript:alert(1)".to_string(),
+            pub_date: String::new(),
+            expire_date: String::new(),
+            pub_time: SystemTime::UNIX_EPOCH,
+            guid: "guid".to_string(),
+            kind: DocumentKind::Html,
+            tags: Vec::new(),
+            notes: vec![Note {
+                id: 1,
+                kind: NoteKind::Note,
+                text: "]]><script>".to_string(),
+            }],
+        }]);
+        let feed = writer.finish();
+
+        assert!(feed.contains("<title>&lt;My&gt; Paket</title>"));
+        assert!(feed.contains("<description>Links &amp; more</description>"));
+        assert!(feed.contains("<title>AT&amp;T &lt;3 you</title>"));
+        assert!(feed.contains("<link>#</link>"));
+        assert!(feed.contains("&lt;p&gt;]]&amp;gt;&amp;lt;script&amp;gt;&lt;/p&gt;"));
+        assert!(!feed.contains("<script>"));
+    }
+
+    #[test]
+    fn items_carry_metadata() {
+        let mut writer = RssWriter::new(
+            "Paket",
+            "Links",
+            "https://paket.example/",
+            SystemTime::UNIX_EPOCH,
+        );
+        writer.set_author("alice");
+        writer.write_feed_items(vec![
+            FeedItem {
+                title: "Paper".to_string(),
+                link: "https://example.com/papers/Paper.PDF?v=2".to_string(),
+                pub_date: String::new(),
+                expire_date: String::new(),
+                pub_time: SystemTime::UNIX_EPOCH,
+                guid: "guid".to_string(),
+                kind: DocumentKind::Html,
+                tags: vec!["rust".to_string()],
+                notes: Vec::new(),
+            },
+            FeedItem {
+                title: "Report".to_string(),
+                link: "https://reports.example.org/download?id=1".to_string(),
+                pub_date: String::new(),
+                expire_date: String::new(),
+                pub_time: SystemTime::UNIX_EPOCH,
+                guid: "report".to_string(),
+                kind: DocumentKind::Pdf,
+                tags: Vec::new(),
+                notes: Vec::new(),
+            },
+        ]);
+        let feed = writer.finish();
+
+        assert!(feed.contains("<atom:link rel=\"self\" type=\"application/rss+xml\" href=\"https://paket.example/feed.xml\"/>"));
+        assert!(feed.contains("<ttl>60</ttl>"));
+        assert!(feed.contains("<category>rust</category>"));
+        assert!(feed.contains(
+            "<enclosure url=\"https://example.com/papers/Paper.PDF?v=2\" type=\"application/pdf\"/>"
+        ));
+        assert!(feed.contains("<enclosure url=\"https://reports.example.org/download?id=1\" type=\"application/pdf\"/>"));
+        assert!(feed.contains("<dc:creator>alice</dc:creator>"));
+        assert!(feed.contains("<source url=\"https://example.com/\">example.com</source>"));
+        assert!(
+            feed.contains(
+                "<source url=\"https://reports.example.org/\">reports.example.org</source>"
+            )
+        );
+        assert!(!feed.contains("length="));
+        assert!(feed.contains("<guid isPermaLink=\"false\">guid</guid>"));
+        assert!(!feed.contains("<content:encoded>"));
+    }
+
+    #[test]
+    fn items_link_to_signed_extend() {
+        let mut db_connection = Connection::open_in_memory().unwrap();
+        setup_tables(&mut db_connection).unwrap();
+        let key = ExtendKey::load(&mut db_connection).unwrap();
+
+        let mut writer = RssWriter::new(
+            "Paket",
+            "Links",
+            "https://paket.example/",
+            SystemTime::UNIX_EPOCH,
+        );
+        writer.set_extend_key(&key);
+        writer.write_feed_items(vec![FeedItem {
+            title: "Example".to_string(),
+            link: "https://example.com/".to_string(),
+            pub_date: String::new(),
+            expire_date: String::new(),
+            pub_time: SystemTime::UNIX_EPOCH,
+            guid: "guid".to_string(),
+            kind: DocumentKind::Html,
+            tags: Vec::new(),
+            notes: Vec::new(),
+        }]);
+        let feed = writer.finish();
+
+        let link = key.link("https://paket.example/", "guid").unwrap();
+        assert!(feed.contains(&link.as_str().replace('&', "&amp;amp;")));
+    }
+
+    #[test]
+    fn notes_are_item_content() {
+        let mut writer = RssWriter::new(
+            "Paket",
+            "Links",
+            "https://paket.example/",
+            SystemTime::UNIX_EPOCH,
+        );
+        writer.write_feed_items(vec![FeedItem {
+            title: "Example".to_string(),
+            link: "https://example.com/".to_string(),
+            pub_date: String::new(),
+            expire_date: String::new(),
+            pub_time: SystemTime::UNIX_EPOCH,
+            guid: "guid".to_string(),
+            kind: DocumentKind::Html,
+            tags: Vec::new(),
+            notes: vec![
+                Note {
+                    id: 1,
+                    kind: NoteKind::Highlight,
+                    text: "Quoted".to_string(),
+                },
+                Note {
+                    id: 2,
+                    kind: NoteKind::Note,
+                    text: "Mine".to_string(),
+                },
+            ],
+        }]);
+        let feed = writer.finish();
+
+        assert!(feed.contains(
+            "<content:encoded>&lt;blockquote&gt;Quoted&lt;/blockquote&gt;&lt;p&gt;Mine&lt;/p&gt;</content:encoded>"
+        ));
+        assert!(feed.contains(
+            "<description>Expires: &lt;blockquote&gt;Quoted&lt;/blockquote&gt;&lt;p&gt;Mine&lt;/p&gt;</description>"
+        ));
+    }
+
+    #[test]
+    fn paged_feed_links() {
+        let mut writer = RssWriter::new(
+            "Paket",
+            "Links",
+            "https://paket.example/",
+            SystemTime::UNIX_EPOCH,
+        );
+        let page = Page {
+            limit: 10,
+            number: 1,
+            before: None,
+        };
+        writer.write_pagination(&Pagination::new(&page, 15, None, ""));
+        let feed = writer.finish();
+
+        assert!(feed.contains("<atom:link rel=\"first\" type=\"application/rss+xml\" href=\"https://paket.example/feed.xml?page=1&amp;limit=10\"/>"));
+        assert!(feed.contains("<atom:link rel=\"next\" type=\"application/rss+xml\" href=\"https://paket.example/feed.xml?page=2&amp;limit=10\"/>"));
+        assert!(feed.contains("<atom:link rel=\"last\" type=\"application/rss+xml\" href=\"https://paket.example/feed.xml?page=2&amp;limit=10\"/>"));
+        assert!(!feed.contains("rel=\"previous\""));
+    }
+
+    #[test]
+    fn collection_items_credit_members() {
+        let mut writer = RssWriter::new_at(
+            "What we read",
+            "Links of the week",
+            "https://reads.example/weekly/",
+            RssWriter::PATH,
+            SystemTime::UNIX_EPOCH,
+        );
+        writer.write_collection_items(vec![CollectionItem {
+            title: "Example".to_string(),
+            link: "https://example.com/".to_string(),
+            guid: "guid".to_string(),
+            kind: DocumentKind::Html,
+            pub_date: String::new(),
+            expire_date: String::new(),
+            saved_by: "alice".to_string(),
+        }]);
+        let feed = writer.finish();
 
-        self.buffer
+        assert!(feed.contains("<atom:link rel=\"self\" type=\"application/rss+xml\" href=\"https://reads.example/weekly/feed.xml\"/>"));
+        assert!(feed.contains("<dc:creator>alice</dc:creator>"));
+        assert!(feed.contains("<source url=\"https://example.com/\">example.com</source>"));
+        assert!(!feed.contains("extend"));
     }
 }
@@ -0,0 +1,252 @@
+use crate::FeedItem;
+
+/// Case-insensitive search over the stored article fields
+pub struct SearchQuery {
+    raw: String,
+    terms: Vec<String>,
+}
+
+/// Piece of a searched text, either matching one of the terms or not
+#[derive(Debug, PartialEq)]
+pub enum Segment<'a> {
+    Plain(&'a str),
+    Match(&'a str),
+}
+
+const TITLE_WEIGHT: usize = 3;
+const TAG_WEIGHT: usize = 2;
+const NOTE_WEIGHT: usize = 1;
+const LINK_WEIGHT: usize = 1;
+
+impl SearchQuery {
+    pub fn parse(raw: &str) -> Self {
+        let terms = raw
+            .split_whitespace()
+            .map(str::to_lowercase)
+            .collect::<Vec<_>>();
+
+        Self {
+            raw: raw.trim().to_string(),
+            terms,
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.raw
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty()
+    }
+
+    /// Keeps items matching every term, best ranked first. Equally ranked items keep their order
+    pub fn rank(&self, items: Vec<FeedItem>) -> Vec<FeedItem> {
+        let mut scored = items
+            .into_iter()
+            .filter_map(|item| self.score(&item).map(|score| (score, item)))
+            .collect::<Vec<_>>();
+
+        scored.sort_by(|(a, _), (b, _)| b.cmp(a));
+
+        scored.into_iter().map(|(_, item)| item).collect()
+    }
+
+    fn score(&self, item: &FeedItem) -> Option<usize> {
+        if self.is_empty() {
+            return None;
+        }
+
+        let mut score = 0;
+
+        for term in &self.terms {
+            let title_matches = count_matches(&item.title, term);
+            let tag_matches = item.tags.iter().filter(|tag| *tag == term).count();
+            let note_matches = item
+                .notes
+                .iter()
+                .map(|note| count_matches(&note.text, term))
+                .sum::<usize>();
+            let link_matches = count_matches(&item.link, term);
+
+            if title_matches + tag_matches + note_matches + link_matches == 0 {
+                return None;
+            }
+
+            score += title_matches * TITLE_WEIGHT
+                + tag_matches * TAG_WEIGHT
+                + note_matches * NOTE_WEIGHT
+                + link_matches * LINK_WEIGHT;
+        }
+
+        Some(score)
+    }
+
+    /// Splits `text` into matching and non matching segments
+    pub fn highlight<'a>(&self, text: &'a str) -> Vec<Segment<'a>> {
+        let mut segments = Vec::new();
+        let mut plain_start = 0;
+        let mut offset = 0;
+
+        while offset < text.len() {
+            let longest_match = self
+                .terms
+                .iter()
+                .filter_map(|term| match_len(&text[offset..], term))
+                .max();
+
+            match longest_match {
+                Some(len) => {
+                    if plain_start < offset {
+                        segments.push(Segment::Plain(&text[plain_start..offset]));
+                    }
+                    segments.push(Segment::Match(&text[offset..offset + len]));
+                    offset += len;
+                    plain_start = offset;
+                }
+                None => {
+                    offset += text[offset..].chars().next().map_or(1, char::len_utf8);
+                }
+            }
+        }
+
+        if plain_start < text.len() {
+            segments.push(Segment::Plain(&text[plain_start..]));
+        }
+
+        segments
+    }
+}
+
+fn count_matches(text: &str, term: &str) -> usize {
+    let mut count = 0;
+    let mut offset = 0;
+
+    while offset < text.len() {
+        match match_len(&text[offset..], term) {
+            Some(len) => {
+                count += 1;
+                offset += len;
+            }
+            None => {
+                offset += text[offset..].chars().next().map_or(1, char::len_utf8);
+            }
+        }
+    }
+
+    count
+}
+
+/// Length in bytes of the `text` prefix matching the lowercase `term`
+fn match_len(text: &str, term: &str) -> Option<usize> {
+    let mut term_chars = term.chars().peekable();
+    let mut len = 0;
+
+    for c in text.chars() {
+        for lower in c.to_lowercase() {
+            if term_chars.next() != Some(lower) {
+                return None;
+            }
+        }
+        len += c.len_utf8();
+
+        if term_chars.peek().is_none() {
+            return Some(len);
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::DocumentKind;
+    use crate::FeedItem;
+    use crate::Note;
+    use crate::NoteKind;
+    use crate::search::SearchQuery;
+    use crate::search::Segment;
+    use std::time::SystemTime;
+
+    fn item(title: &str, link: &str) -> FeedItem {
+        FeedItem {
+            title: title.to_string(),
+            link: link.to_string(),
+            pub_date: String::new(),
+            expire_date: String::new(),
+            pub_time: SystemTime::UNIX_EPOCH,
+            guid: title.to_string(),
+            kind: DocumentKind::Html,
+            tags: Vec::new(),
+            notes: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn rank_requires_all_terms_and_prefers_titles() {
+        let query = SearchQuery::parse("rust async");
+
+        let items = vec![
+            item("Async in Python", "https://example.com/rust"),
+            item("Rust: async book", "https://example.com/book"),
+            item("Rust book", "https://example.com/book"),
+        ];
+
+        let titles = query
+            .rank(items)
+            .into_iter()
+            .map(|item| item.title)
+            .collect::<Vec<_>>();
+
+        assert_eq!(titles, vec!["Rust: async book", "Async in Python"]);
+    }
+
+    #[test]
+    fn rank_searches_notes() {
+        let query = SearchQuery::parse("ownership");
+
+        let mut noted = item("Rust book", "https://example.com/book");
+        noted.notes.push(Note {
+            id: 1,
+            kind: NoteKind::Highlight,
+            text: "Ownership is Rust's most unique feature".to_string(),
+        });
+        let items = vec![item("Async book", "https://example.com/async"), noted];
+
+        let titles = query
+            .rank(items)
+            .into_iter()
+            .map(|item| item.title)
+            .collect::<Vec<_>>();
+
+        assert_eq!(titles, vec!["Rust book"]);
+    }
+
+    #[test]
+    fn highlight_case_insensitive() {
+        let query = SearchQuery::parse("ÜBER rust");
+
+        let segments = query.highlight("Über Rust and RUST");
+
+        assert_eq!(
+            segments,
+            vec![
+                Segment::Match("Über"),
+                Segment::Plain(" "),
+                Segment::Match("Rust"),
+                Segment::Plain(" and "),
+                Segment::Match("RUST"),
+            ]
+        );
+    }
+
+    #[test]
+    fn empty_query_matches_nothing() {
+        let query = SearchQuery::parse("   ");
+
+        assert!(
+            query
+                .rank(vec![item("Anything", "https://example.com")])
+                .is_empty()
+        );
+    }
+}
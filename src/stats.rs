@@ -0,0 +1,232 @@
+use crate::user::User;
+use duckdb::Connection;
+use duckdb::params;
+use serde::Serialize;
+
+const TOP_DOMAINS: usize = 10;
+const SAVES_PER_DAY_DAYS: u32 = 30;
+
+/// Aggregates over a user's articles and activity history
+#[derive(Debug, Serialize)]
+pub struct Stats {
+    pub live_articles: i64,
+    pub unread_articles: i64,
+    pub top_domains: Vec<DomainCount>,
+    /// Last 30 days including today, oldest first
+    pub saves_per_day: Vec<DayCount>,
+    /// Among articles still stored, including trash
+    pub median_hours_to_read: Option<f64>,
+    pub expired_unread_percent: Option<f64>,
+    /// Most live articles at the end of a day
+    pub largest_backlog: Option<DayCount>,
+    pub fetch_failure_percent: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DomainCount {
+    pub domain: String,
+    pub count: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DayCount {
+    pub day: String,
+    pub count: i64,
+}
+
+pub fn fetch_stats(db_connection: &mut Connection, user: &User) -> anyhow::Result<Stats> {
+    let (live_articles, unread_articles) = db_connection.query_row(
+        "SELECT
+        count(*),
+        count(*) FILTER (WHERE read_at IS NULL)
+        FROM articles
+        WHERE user_id = ? AND deleted_at IS NULL",
+        [user.id],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+
+    let median_hours_to_read = db_connection.query_row(
+        "SELECT
+        median(epoch(CAST(read_at AS TIMESTAMP)) - epoch(CAST(timestamp AS TIMESTAMP))) / 3600
+        FROM articles
+        WHERE user_id = ? AND read_at IS NOT NULL",
+        [user.id],
+        |row| row.get(0),
+    )?;
+
+    let (saved, expired_unread, fetch_failed): (i64, i64, i64) = db_connection.query_row(
+        "SELECT
+        coalesce(sum(count) FILTER (WHERE kind = 'saved'), 0),
+        coalesce(sum(count) FILTER (WHERE kind = 'expired_unread'), 0),
+        coalesce(sum(count) FILTER (WHERE kind = 'fetch_failed'), 0)
+        FROM activity
+        WHERE user_id = ?",
+        [user.id],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+    )?;
+
+    Ok(Stats {
+        live_articles,
+        unread_articles,
+        top_domains: fetch_top_domains(db_connection, user)?,
+        saves_per_day: fetch_saves_per_day(db_connection, user)?,
+        median_hours_to_read,
+        expired_unread_percent: percent(expired_unread, saved),
+        largest_backlog: fetch_largest_backlog(db_connection, user)?,
+        fetch_failure_percent: percent(fetch_failed, saved + fetch_failed),
+    })
+}
+
+fn fetch_top_domains(
+    db_connection: &mut Connection,
+    user: &User,
+) -> anyhow::Result<Vec<DomainCount>> {
+    let mut select_stmt = db_connection.prepare(
+        "SELECT
+        regexp_extract(link, '^[a-z]+://([^/:?#]+)', 1) AS domain, count(*) AS count
+        FROM articles
+        WHERE user_id = ? AND deleted_at IS NULL
+        GROUP BY domain
+        ORDER BY count DESC, domain ASC
+        LIMIT ?",
+    )?;
+
+    let mut rows = select_stmt.query([user.id, TOP_DOMAINS as i64])?;
+
+    let mut items = Vec::with_capacity(TOP_DOMAINS);
+    while let Some(row) = rows.next()? {
+        let item = DomainCount {
+            domain: row.get(0)?,
+            count: row.get(1)?,
+        };
+        items.push(item);
+    }
+
+    Ok(items)
+}
+
+fn fetch_saves_per_day(
+    db_connection: &mut Connection,
+    user: &User,
+) -> anyhow::Result<Vec<DayCount>> {
+    let mut select_stmt = db_connection.prepare(
+        "SELECT
+        strftime(days.day, '%Y-%m-%d'),
+        coalesce(sum(activity.count), 0)
+        FROM generate_series(
+            date_trunc('day', CAST(current_timestamp AS TIMESTAMP)) - INTERVAL (?) DAY,
+            date_trunc('day', CAST(current_timestamp AS TIMESTAMP)),
+            INTERVAL 1 DAY
+        ) AS days(day)
+        LEFT JOIN activity ON CAST(activity.day AS TIMESTAMP) = days.day AND activity.kind = 'saved'
+        AND activity.user_id = ?
+        GROUP BY days.day
+        ORDER BY days.day ASC",
+    )?;
+
+    let mut rows = select_stmt.query(params![SAVES_PER_DAY_DAYS - 1, user.id])?;
+
+    let mut items = Vec::with_capacity(SAVES_PER_DAY_DAYS as usize);
+    while let Some(row) = rows.next()? {
+        let item = DayCount {
+            day: row.get(0)?,
+            count: row.get(1)?,
+        };
+        items.push(item);
+    }
+
+    Ok(items)
+}
+
+fn fetch_largest_backlog(
+    db_connection: &mut Connection,
+    user: &User,
+) -> anyhow::Result<Option<DayCount>> {
+    let mut select_stmt = db_connection.prepare(
+        "SELECT
+        strftime(day, '%Y-%m-%d'),
+        sum(delta) OVER (ORDER BY day) AS backlog
+        FROM (
+            SELECT day, sum(CASE WHEN kind = 'saved' THEN count ELSE -count END) AS delta
+            FROM activity
+            WHERE user_id = ? AND kind IN ('saved', 'deleted', 'expired')
+            GROUP BY day
+        )
+        ORDER BY backlog DESC, day DESC
+        LIMIT 1",
+    )?;
+
+    let mut rows = select_stmt.query([user.id])?;
+
+    let item = match rows.next()? {
+        Some(row) => Some(DayCount {
+            day: row.get(0)?,
+            count: row.get::<_, f64>(1)? as i64,
+        }),
+        None => None,
+    };
+
+    Ok(item)
+}
+
+fn percent(part: i64, total: i64) -> Option<f64> {
+    if total <= 0 {
+        return None;
+    }
+
+    Some(part as f64 * 100.0 / total as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::setup_tables;
+    use crate::stats::fetch_stats;
+    use crate::user::User;
+    use duckdb::Connection;
+
+    #[test]
+    fn stats_from_activity_history() {
+        let mut db_connection = Connection::open_in_memory().unwrap();
+        setup_tables(&mut db_connection).unwrap();
+
+        db_connection
+            .execute_batch(
+                "
+                INSERT INTO activity (day, kind, count) VALUES
+                ('2026-01-01', 'saved', 5),
+                ('2026-01-02', 'saved', 3),
+                ('2026-01-02', 'deleted', 1),
+                ('2026-01-03', 'expired', 4),
+                ('2026-01-03', 'expired_unread', 2),
+                ('2026-01-03', 'fetch_failed', 2);
+                INSERT INTO articles (timestamp, read_at, title, link, guid) VALUES
+                (CAST('2026-01-01 00:00:00' AS TIMESTAMP), CAST('2026-01-01 02:00:00' AS TIMESTAMP), 'a', 'https://a.example/1', '1'),
+                (CAST('2026-01-01 00:00:00' AS TIMESTAMP), CAST('2026-01-01 04:00:00' AS TIMESTAMP), 'b', 'https://a.example/2', '2'),
+                (CAST('2026-01-01 00:00:00' AS TIMESTAMP), NULL, 'c', 'http://b.example:8080/3', '3');",
+            )
+            .unwrap();
+
+        let user = User {
+            id: 0,
+            name: "default".to_string(),
+            ttl: 60,
+            feed_name: "My Paket".to_string(),
+            feed_desc: "My links".to_string(),
+        };
+        let stats = fetch_stats(&mut db_connection, &user).unwrap();
+
+        assert_eq!(stats.live_articles, 3);
+        assert_eq!(stats.unread_articles, 1);
+        assert_eq!(stats.top_domains[0].domain, "a.example");
+        assert_eq!(stats.top_domains[0].count, 2);
+        assert_eq!(stats.top_domains[1].domain, "b.example");
+        assert_eq!(stats.saves_per_day.len(), 30);
+        assert_eq!(stats.median_hours_to_read, Some(3.0));
+        assert_eq!(stats.expired_unread_percent, Some(25.0));
+        assert_eq!(stats.fetch_failure_percent, Some(20.0));
+
+        let largest_backlog = stats.largest_backlog.unwrap();
+        assert_eq!(largest_backlog.day, "2026-01-02");
+        assert_eq!(largest_backlog.count, 7);
+    }
+}
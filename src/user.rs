@@ -0,0 +1,331 @@
+use crate::Args;
+use anyhow::bail;
+use argh::FromArgs;
+use duckdb::Connection;
+use duckdb::OptionalExt;
+use duckdb::params;
+use uuid::Uuid;
+
+/// Owner of the articles saved without credentials or with `--basic-auth`
+pub const DEFAULT_USER_ID: i64 = 0;
+
+/// Owner of a library, with the settings the global options are the defaults of
+#[derive(Debug, Clone)]
+pub struct User {
+    pub id: i64,
+    pub name: String,
+    /// Time to live in days
+    pub ttl: u32,
+    pub feed_name: String,
+    pub feed_desc: String,
+}
+
+impl User {
+    /// Guid of the user's article at the url. The default user keeps the guids saved
+    /// before there were users, the others get their own
+    pub fn guid(&self, url: &str) -> String {
+        let namespace = if self.id == DEFAULT_USER_ID {
+            Uuid::NAMESPACE_URL
+        } else {
+            Uuid::new_v5(
+                &Uuid::NAMESPACE_URL,
+                format!("paket:user:{}", self.id).as_bytes(),
+            )
+        };
+
+        Uuid::new_v5(&namespace, url.as_bytes()).to_string()
+    }
+}
+
+/// Manage users and their settings
+#[derive(Debug, Clone, FromArgs)]
+#[argh(subcommand, name = "user")]
+pub struct UserCommand {
+    #[argh(subcommand)]
+    action: UserAction,
+}
+
+#[derive(Debug, Clone, FromArgs)]
+#[argh(subcommand)]
+enum UserAction {
+    Create(CreateUser),
+    List(ListUsers),
+    Update(UpdateUser),
+    Delete(DeleteUser),
+}
+
+/// Create a user. Settings left out follow the global options
+#[derive(Debug, Clone, FromArgs)]
+#[argh(subcommand, name = "create")]
+struct CreateUser {
+    /// name of the user
+    #[argh(positional)]
+    name: String,
+
+    /// time to live in days
+    #[argh(option)]
+    ttl: Option<u32>,
+
+    /// feed name
+    #[argh(option)]
+    feed_name: Option<String>,
+
+    /// feed description
+    #[argh(option)]
+    feed_desc: Option<String>,
+}
+
+/// List users and their settings
+#[derive(Debug, Clone, FromArgs)]
+#[argh(subcommand, name = "list")]
+struct ListUsers {}
+
+/// Change the settings of a user. Settings left out stay as they are
+#[derive(Debug, Clone, FromArgs)]
+#[argh(subcommand, name = "update")]
+struct UpdateUser {
+    /// name of the user
+    #[argh(positional)]
+    name: String,
+
+    /// time to live in days
+    #[argh(option)]
+    ttl: Option<u32>,
+
+    /// feed name
+    #[argh(option)]
+    feed_name: Option<String>,
+
+    /// feed description
+    #[argh(option)]
+    feed_desc: Option<String>,
+}
+
+/// Delete a user with their articles and tokens
+#[derive(Debug, Clone, FromArgs)]
+#[argh(subcommand, name = "delete")]
+struct DeleteUser {
+    /// name of the user
+    #[argh(positional)]
+    name: String,
+}
+
+pub fn run_user_command(
+    db_connection: &mut Connection,
+    command: &UserCommand,
+    args: &Args,
+) -> anyhow::Result<()> {
+    match &command.action {
+        UserAction::Create(create) => {
+            create_user(
+                db_connection,
+                &create.name,
+                create.ttl,
+                create.feed_name.as_deref(),
+                create.feed_desc.as_deref(),
+            )?;
+        }
+        UserAction::List(_) => {
+            for user in fetch_users(db_connection, args)? {
+                println!(
+                    "{}\tttl {} days\tfeed {} ({})",
+                    user.name, user.ttl, user.feed_name, user.feed_desc
+                );
+            }
+        }
+        UserAction::Update(update) => {
+            let updated = update_user(
+                db_connection,
+                &update.name,
+                update.ttl,
+                update.feed_name.as_deref(),
+                update.feed_desc.as_deref(),
+            )?;
+            if !updated {
+                bail!("no user {}", update.name);
+            }
+        }
+        UserAction::Delete(delete) => {
+            if !delete_user(db_connection, &delete.name)? {
+                bail!("no user {}", delete.name);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn create_user(
+    db_connection: &mut Connection,
+    name: &str,
+    ttl: Option<u32>,
+    feed_name: Option<&str>,
+    feed_desc: Option<&str>,
+) -> anyhow::Result<i64> {
+    let name = name.trim();
+    if name.is_empty() || name.contains(':') {
+        bail!("invalid user name {name}");
+    }
+    if find_user_id(db_connection, name)?.is_some() {
+        bail!("user {name} exists");
+    }
+
+    let id = db_connection.query_row(
+        "INSERT INTO users (name, ttl, feed_name, feed_desc, created_at)
+        VALUES (?, ?, ?, ?, current_timestamp)
+        RETURNING id",
+        params![name, ttl, feed_name, feed_desc],
+        |row| row.get(0),
+    )?;
+
+    Ok(id)
+}
+
+fn update_user(
+    db_connection: &mut Connection,
+    name: &str,
+    ttl: Option<u32>,
+    feed_name: Option<&str>,
+    feed_desc: Option<&str>,
+) -> anyhow::Result<bool> {
+    let updated = db_connection.execute(
+        "UPDATE users SET
+        ttl = coalesce(?, ttl),
+        feed_name = coalesce(?, feed_name),
+        feed_desc = coalesce(?, feed_desc)
+        WHERE name = ?",
+        params![ttl, feed_name, feed_desc, name],
+    )?;
+    Ok(updated > 0)
+}
+
+/// The default user can't be deleted, it owns what is saved without credentials
+fn delete_user(db_connection: &mut Connection, name: &str) -> anyhow::Result<bool> {
+    let Some(id) = find_user_id(db_connection, name)? else {
+        return Ok(false);
+    };
+    if id == DEFAULT_USER_ID {
+        bail!("the default user can't be deleted");
+    }
+
+    let tx = db_connection.transaction()?;
+    tx.execute(
+        "DELETE FROM notes WHERE guid IN (SELECT guid FROM articles WHERE user_id = ?)",
+        [id],
+    )?;
+    tx.execute(
+        "DELETE FROM tags WHERE guid IN (SELECT guid FROM articles WHERE user_id = ?)",
+        [id],
+    )?;
+    tx.execute("DELETE FROM articles WHERE user_id = ?", [id])?;
+    tx.execute("DELETE FROM activity WHERE user_id = ?", [id])?;
+    tx.execute("DELETE FROM tokens WHERE user_id = ?", [id])?;
+    tx.execute("DELETE FROM feed_tokens WHERE user_id = ?", [id])?;
+    tx.execute("DELETE FROM collection_members WHERE user_id = ?", [id])?;
+    tx.execute("DELETE FROM users WHERE id = ?", [id])?;
+    tx.commit()?;
+
+    Ok(true)
+}
+
+pub fn find_user_id(db_connection: &mut Connection, name: &str) -> anyhow::Result<Option<i64>> {
+    let id = db_connection
+        .query_row("SELECT id FROM users WHERE name = ?", [name], |row| {
+            row.get(0)
+        })
+        .optional()?;
+    Ok(id)
+}
+
+/// The user with unset settings taken from the global options
+pub fn fetch_user(
+    db_connection: &mut Connection,
+    id: i64,
+    args: &Args,
+) -> anyhow::Result<Option<User>> {
+    let user = db_connection
+        .query_row(
+            "SELECT id, name, ttl, feed_name, feed_desc FROM users WHERE id = ?",
+            [id],
+            |row| read_user(row, args),
+        )
+        .optional()?;
+    Ok(user)
+}
+
+/// All users, oldest first
+pub fn fetch_users(db_connection: &mut Connection, args: &Args) -> anyhow::Result<Vec<User>> {
+    let mut select_stmt = db_connection.prepare(
+        "SELECT id, name, ttl, feed_name, feed_desc FROM users
+        ORDER BY id ASC",
+    )?;
+
+    let users = select_stmt
+        .query_map([], |row| read_user(row, args))?
+        .collect::<Result<_, _>>()?;
+
+    Ok(users)
+}
+
+fn read_user(row: &duckdb::Row, args: &Args) -> duckdb::Result<User> {
+    Ok(User {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        ttl: row.get::<_, Option<u32>>(2)?.unwrap_or(args.ttl),
+        feed_name: row
+            .get::<_, Option<String>>(3)?
+            .unwrap_or_else(|| args.name.clone()),
+        feed_desc: row
+            .get::<_, Option<String>>(4)?
+            .unwrap_or_else(|| args.desc.clone()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Args;
+    use crate::setup_tables;
+    use crate::user::DEFAULT_USER_ID;
+    use crate::user::create_user;
+    use crate::user::delete_user;
+    use crate::user::fetch_user;
+    use crate::user::fetch_users;
+    use crate::user::update_user;
+    use argh::FromArgs;
+    use duckdb::Connection;
+
+    #[test]
+    fn users_fall_back_to_global_options() {
+        let mut db_connection = Connection::open_in_memory().unwrap();
+        setup_tables(&mut db_connection).unwrap();
+        let args =
+            Args::from_args(&["paket"], &["-l", "https://paket.example/", "--ttl", "30"]).unwrap();
+
+        let id = create_user(&mut db_connection, "alice", None, Some("Alice"), None).unwrap();
+        assert!(create_user(&mut db_connection, "alice", None, None, None).is_err());
+        assert!(create_user(&mut db_connection, "a:b", None, None, None).is_err());
+
+        let alice = fetch_user(&mut db_connection, id, &args).unwrap().unwrap();
+        assert_eq!(alice.ttl, 30);
+        assert_eq!(alice.feed_name, "Alice");
+        assert_eq!(alice.feed_desc, "My links");
+
+        assert!(update_user(&mut db_connection, "alice", Some(7), None, None).unwrap());
+        let alice = fetch_user(&mut db_connection, id, &args).unwrap().unwrap();
+        assert_eq!(alice.ttl, 7);
+        assert_eq!(alice.feed_name, "Alice");
+
+        let default = fetch_user(&mut db_connection, DEFAULT_USER_ID, &args)
+            .unwrap()
+            .unwrap();
+        assert_ne!(
+            default.guid("https://example.com/"),
+            alice.guid("https://example.com/")
+        );
+        assert_eq!(fetch_users(&mut db_connection, &args).unwrap().len(), 2);
+
+        assert!(delete_user(&mut db_connection, "default").is_err());
+        assert!(delete_user(&mut db_connection, "alice").unwrap());
+        assert!(!delete_user(&mut db_connection, "alice").unwrap());
+    }
+}
@@ -0,0 +1,308 @@
+// Service worker of the Paket pages. Pages come from the network and are kept for
+// reading offline, saves and deletes made offline are queued and sent later
+
+const CACHE = 'paket-v1';
+// Cache entry naming whom the kept pages were made for, see `Paket-Viewer`
+const VIEWER = '/paket-viewer';
+const QUEUE_DB = 'paket-queue';
+const QUEUE_STORE = 'requests';
+const SYNC_TAG = 'paket-queue';
+
+// Paket keeps no copy of the articles, offline there are only its own pages
+const PAGES = [
+  '/feed.html',
+  '/trash.html',
+  '/stats',
+  '/search',
+  '/install',
+  '/manifest.webmanifest',
+  '/app.js',
+  '/icon.svg',
+];
+
+const QUEUED = ['/save', '/add', '/share', '/delete'];
+
+self.addEventListener('install', function(event) {
+  // the feed may need credentials first, it is cached on the first visit then
+  event.waitUntil(
+    caches.open(CACHE)
+      .then(function(cache) {
+        return cache.addAll(['/feed.html', '/app.js', '/icon.svg', '/manifest.webmanifest']);
+      })
+      .catch(function() {})
+  );
+  self.skipWaiting();
+});
+
+self.addEventListener('activate', function(event) {
+  event.waitUntil(
+    caches.keys()
+      .then(function(keys) {
+        return Promise.all(keys.filter(function(key) {
+          return key !== CACHE;
+        }).map(function(key) {
+          return caches.delete(key);
+        }));
+      })
+      .then(function() {
+        return self.clients.claim();
+      })
+  );
+});
+
+self.addEventListener('fetch', function(event) {
+  const request = event.request;
+  const url = new URL(request.url);
+
+  if (url.origin !== self.location.origin) {
+    return;
+  }
+
+  if (QUEUED.includes(url.pathname)) {
+    event.respondWith(sendOrQueue(request));
+  } else if (request.method === 'GET' && PAGES.includes(url.pathname)) {
+    event.respondWith(networkFirst(request));
+  }
+});
+
+self.addEventListener('sync', function(event) {
+  if (event.tag === SYNC_TAG) {
+    event.waitUntil(flush());
+  }
+});
+
+self.addEventListener('message', function(event) {
+  if (event.data === 'flush') {
+    event.waitUntil(flush().catch(function() {}));
+  }
+});
+
+// The latest page when online, the cached one otherwise. Pages with a query fall
+// back to the same page without it, like a filtered feed to the whole feed
+function networkFirst(request) {
+  return fetch(request)
+    .then(function(response) {
+      if (response.ok) {
+        keep(request, response.clone());
+      }
+      return response;
+    })
+    .catch(function() {
+      return caches.match(request)
+        .then(function(response) {
+          return response || caches.match(request, {ignoreSearch: true});
+        })
+        .then(function(response) {
+          return response || offlinePage('Offline, this page was not read before.', 503);
+        });
+    });
+}
+
+// Pages made for another session or user replace everything kept before them,
+// so that signing out or in as someone else doesn't show the last one's articles
+function keep(request, response) {
+  const viewer = response.headers.get('Paket-Viewer');
+
+  return caches.open(CACHE).then(function(cache) {
+    if (!viewer) {
+      return cache.put(request, response);
+    }
+
+    return cache.match(VIEWER)
+      .then(function(kept) {
+        return kept ? kept.text() : null;
+      })
+      .then(function(kept) {
+        if (kept === viewer) {
+          return;
+        }
+        return cache.keys()
+          .then(function(keys) {
+            return Promise.all(keys.map(function(key) {
+              return cache.delete(key);
+            }));
+          })
+          .then(function() {
+            return cache.put(VIEWER, new Response(viewer));
+          });
+      })
+      .then(function() {
+        return cache.put(request, response);
+      });
+  });
+}
+
+function sendOrQueue(request) {
+  const queued = request.clone();
+
+  return fetch(request).catch(function() {
+    return queue(queued).then(function() {
+      if (queued.mode !== 'navigate') {
+        return new Response(null, {status: 202});
+      }
+      return offlinePage('Offline, this is sent once the connection is back.', 202);
+    });
+  });
+}
+
+function queue(request) {
+  return request.text()
+    .then(function(body) {
+      const entry = {
+        url: request.url,
+        method: request.method,
+        headers: Array.from(request.headers.entries()),
+        body: body,
+      };
+      return withStore('readwrite', function(store) {
+        return store.add(entry);
+      });
+    })
+    .then(function() {
+      if (self.registration.sync) {
+        return self.registration.sync.register(SYNC_TAG);
+      }
+    });
+}
+
+// Sends the queued requests in order and stops at the first that isn't through yet:
+// the network still fails, credentials are missing or the server has trouble. The
+// pages are told about the latter. Other answers leave the queue, retrying wouldn't
+// change them
+let flushing = null;
+
+function flush() {
+  if (!flushing) {
+    flushing = withStore('readonly', function(store) {
+      return store.getAll();
+    })
+      .then(function(entries) {
+        return entries.reduce(function(previous, entry) {
+          return previous.then(function() {
+            return send(entry);
+          });
+        }, Promise.resolve());
+      })
+      .catch(function(err) {
+        if (!(err instanceof Response)) {
+          throw err;
+        }
+        return tell({type: 'unsent', status: err.status}).then(function() {
+          throw err;
+        });
+      })
+      .finally(function() {
+        flushing = null;
+      });
+  }
+  return flushing;
+}
+
+// Rejects with the response when the entry has to stay queued. Form tokens expire
+// when the server restarts, a refused form is sent once more with a fresh one
+function send(entry) {
+  return resend(entry)
+    .then(function(response) {
+      if (response.status !== 403 || !hasToken(entry)) {
+        return response;
+      }
+      return freshToken().then(function(token) {
+        return token ? resend(withToken(entry, token)) : response;
+      });
+    })
+    .then(function(response) {
+      if (isUnsent(response)) {
+        throw response;
+      }
+      return withStore('readwrite', function(store) {
+        return store.delete(entry.id);
+      });
+    });
+}
+
+function resend(entry) {
+  return fetch(entry.url, {
+    method: entry.method,
+    headers: entry.headers,
+    body: entry.method === 'GET' ? undefined : entry.body,
+    credentials: 'same-origin',
+    redirect: 'manual',
+  });
+}
+
+function isUnsent(response) {
+  return response.status === 401 || response.status === 403 || response.status >= 500;
+}
+
+function hasToken(entry) {
+  return entry.method !== 'GET' && new URLSearchParams(entry.body).has('csrf');
+}
+
+function withToken(entry, token) {
+  const body = new URLSearchParams(entry.body);
+  body.set('csrf', token);
+  return Object.assign({}, entry, {body: body.toString()});
+}
+
+// Token of the session from a form of the feed
+function freshToken() {
+  return fetch('/feed.html', {credentials: 'same-origin'})
+    .then(function(response) {
+      return response.ok ? response.text() : '';
+    })
+    .then(function(page) {
+      const found = page.match(/name="csrf" value="([0-9a-f]+)"/);
+      return found ? found[1] : null;
+    })
+    .catch(function() {
+      return null;
+    });
+}
+
+function tell(message) {
+  return self.clients.matchAll({type: 'window'}).then(function(clients) {
+    clients.forEach(function(client) {
+      client.postMessage(message);
+    });
+  });
+}
+
+// Runs one transaction on the queue and resolves with the result of its request
+function withStore(mode, use) {
+  return new Promise(function(resolve, reject) {
+    const open = indexedDB.open(QUEUE_DB, 1);
+
+    open.onupgradeneeded = function() {
+      open.result.createObjectStore(QUEUE_STORE, {keyPath: 'id', autoIncrement: true});
+    };
+    open.onerror = function() {
+      reject(open.error);
+    };
+    open.onsuccess = function() {
+      const db = open.result;
+      const transaction = db.transaction(QUEUE_STORE, mode);
+      const result = use(transaction.objectStore(QUEUE_STORE));
+
+      transaction.oncomplete = function() {
+        db.close();
+        resolve(result.result);
+      };
+      transaction.onerror = function() {
+        db.close();
+        reject(transaction.error);
+      };
+    };
+  });
+}
+
+function offlinePage(message, status) {
+  const page = '<!DOCTYPE html><html lang="en"><head><meta charset="UTF-8">' +
+    '<meta name="viewport" content="width=device-width, initial-scale=1.0">' +
+    '<title>Paket</title></head><body><p>' + message + '</p>' +
+    '<p><a href="/feed.html">Feed</a></p></body></html>';
+
+  return new Response(page, {
+    status: status,
+    headers: {'Content-Type': 'text/html'},
+  });
+}